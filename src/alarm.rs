@@ -0,0 +1,129 @@
+//! Limits on channel readings, and the actions taken when they are crossed
+
+use crate::extractor::{Config, Data};
+use std::process::{Command, Stdio};
+
+/// Upper and lower limits on the readings of a channel
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Limits {
+    pub lower: Option<f64>,
+    pub upper: Option<f64>,
+}
+
+impl Limits {
+    pub fn is_set(&self) -> bool {
+        self.lower.is_some() || self.upper.is_some()
+    }
+
+    /// Gets the limit that a reading is outside of, if any.
+    pub fn violated_by(&self, value: f64) -> Option<f64> {
+        match (self.lower, self.upper) {
+            (Some(lower), _) if value < lower => Some(lower),
+            (_, Some(upper)) if value > upper => Some(upper),
+            _ => None,
+        }
+    }
+}
+
+/// What to do when a channel crosses one of its limits
+#[derive(Clone, Debug, Default)]
+pub struct AlarmActions {
+    /// Shell command to run
+    pub command: Option<String>,
+    /// Prints a message to stderr
+    pub stderr: bool,
+    /// Exits cliplot with this status
+    pub exit_code: Option<i32>,
+}
+
+/// Runs a shell command in the background with extra environment variables, logging if it could not start.
+pub fn run_command(command: &str, env: &[(&str, String)]) {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+
+    cmd.arg(command)
+        .envs(env.iter().map(|(k, v)| (*k, v)))
+        .stdin(Stdio::null());
+
+    // Do not wait on the command, so a slow command does not stall extraction
+    if let Err(e) = cmd.spawn() {
+        log::error!("Failed to run alarm command `{command}`: {e}");
+    }
+}
+
+/// Tracks which channels are outside their limits
+pub struct Alarms {
+    names: Vec<String>,
+    limits: Vec<Limits>,
+    /// Whether each channel was outside its limits at its last reading
+    violating: Vec<bool>,
+    actions: AlarmActions,
+}
+
+impl Alarms {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            names: config.channel_labels(),
+            limits: config.channels.iter().map(|c| c.limits).collect(),
+            violating: vec![false; config.channels.len()],
+            actions: config.alarm.clone(),
+        }
+    }
+
+    /// Checks a batch of readings against their limits, acting on any channel that just crossed one.
+    ///
+    /// Actions only happen when a channel goes out of its limits, not for every reading while it stays
+    /// there. Returns the status to exit with if that action is configured.
+    pub fn check(&mut self, batch: &[Data]) -> Option<i32> {
+        let mut exit = None;
+
+        for reading in batch {
+            // Channels added while running have no limits, and text has no limits
+            let limit = match (self.limits.get(reading.channel), reading.data.number()) {
+                (Some(limits), Some(data)) => limits.violated_by(data),
+                _ => continue,
+            };
+            let was_violating = self.violating[reading.channel];
+            self.violating[reading.channel] = limit.is_some();
+
+            let limit = match limit {
+                Some(limit) if !was_violating => limit,
+                _ => continue,
+            };
+            let name = &self.names[reading.channel];
+
+            log::warn!("{name} read {} outside limit {limit}", reading.data);
+            if self.actions.stderr {
+                eprintln!(
+                    "cliplot: {name} read {} outside limit {limit} at {}",
+                    reading.data,
+                    reading.stamp.to_rfc3339()
+                );
+            }
+
+            if let Some(command) = &self.actions.command {
+                run_command(
+                    command,
+                    &[
+                        ("CLIPLOT_ALARM", "limit".to_string()),
+                        ("CLIPLOT_CHANNEL", reading.channel.to_string()),
+                        ("CLIPLOT_NAME", name.clone()),
+                        ("CLIPLOT_VALUE", reading.data.to_string()),
+                        ("CLIPLOT_LIMIT", limit.to_string()),
+                    ],
+                );
+            }
+
+            exit = exit.or(self.actions.exit_code);
+        }
+
+        exit
+    }
+}
@@ -0,0 +1,74 @@
+//! Array channels, where each match is a whole frame of values such as a lidar scan or a spectrum
+
+use crate::extractor::{Config, Data, Source, Value};
+use std::sync::Arc;
+
+/// Parses a captured array such as `[1.2, 3.4, 5.6]` or `1.2 3.4 5.6`.
+///
+/// Values may be separated by commas, semicolons or whitespace, and surrounding brackets are optional.
+/// Returns None if the array is empty or any value is not a number.
+pub fn parse_array(text: &str) -> Option<Arc<[f64]>> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix('[')
+        .and_then(|t| t.strip_suffix(']'))
+        .unwrap_or(text);
+
+    let values: Vec<f64> = text
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<f64>().ok())
+        .collect::<Option<_>>()?;
+
+    (!values.is_empty()).then(|| values.into())
+}
+
+/// Channels that take one element of an array channel
+pub struct FanOut {
+    /// (array channel, index in the array, element channel)
+    elements: Vec<(usize, usize, usize)>,
+}
+
+impl FanOut {
+    pub fn new(config: &Config) -> Self {
+        let elements = config
+            .channels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match c.source {
+                Source::Element(array, index) => Some((array, index, i)),
+                _ => None,
+            })
+            .collect();
+
+        Self { elements }
+    }
+
+    /// Adds a reading for each element channel of the arrays in a batch. Frames too short to have an
+    /// element give no reading for it.
+    pub fn process(&self, batch: &mut Vec<Data>) {
+        if self.elements.is_empty() {
+            return;
+        }
+
+        let mut readings = Vec::new();
+        for reading in batch.iter() {
+            let values = match &reading.data {
+                Value::Array(values) => values,
+                _ => continue,
+            };
+
+            for (_, index, channel) in self.elements.iter().filter(|e| e.0 == reading.channel) {
+                if let Some(value) = values.get(*index) {
+                    readings.push(Data {
+                        stamp: reading.stamp,
+                        channel: *channel,
+                        data: Value::Number(*value),
+                    });
+                }
+            }
+        }
+
+        batch.extend(readings);
+    }
+}
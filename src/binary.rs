@@ -0,0 +1,456 @@
+//! Decoding of binary packets described by a layout file, for boards that stream raw structs
+
+use crate::crc::Checksum;
+use crate::framing::Framing;
+use std::str::FromStr;
+
+/// Longest payload accepted from a length field, so a corrupted length does not stall decoding
+const MAX_PAYLOAD_LEN: usize = 4096;
+
+/// How the bytes of a field are interpreted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    Unsigned,
+    Signed,
+    Float,
+}
+
+/// Type of a field in a packet, such as `u16le` or `f32be`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FieldType {
+    kind: Kind,
+    /// Size in bytes
+    size: usize,
+    little_endian: bool,
+}
+
+impl FieldType {
+    /// Decodes a field from exactly `size` bytes.
+    fn decode(&self, bytes: &[u8]) -> f64 {
+        // Widen to 8 bytes in little endian order, sign extending signed fields
+        let mut wide = [0u8; 8];
+        wide[..self.size].copy_from_slice(bytes);
+        if !self.little_endian {
+            wide[..self.size].reverse();
+        }
+        if self.kind == Kind::Signed && wide[self.size - 1] & 0x80 != 0 {
+            wide[self.size..].fill(0xFF);
+        }
+
+        match (self.kind, self.size) {
+            (Kind::Float, 4) => f32::from_le_bytes(wide[..4].try_into().unwrap()) as f64,
+            (Kind::Float, _) => f64::from_le_bytes(wide),
+            (Kind::Signed, _) => i64::from_le_bytes(wide) as f64,
+            (Kind::Unsigned, _) => u64::from_le_bytes(wide) as f64,
+        }
+    }
+}
+
+impl FromStr for FieldType {
+    type Err = String;
+
+    /// Parses `u8`, `i8`, or a type of `u16`, `i16`, `u32`, `i32`, `u64`, `i64`, `f32` or `f64` followed by
+    /// `le` or `be`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, little_endian) = match (s.strip_suffix("le"), s.strip_suffix("be")) {
+            (Some(name), _) => (name, true),
+            (_, Some(name)) => (name, false),
+            _ => (s, true),
+        };
+
+        let (kind, size) = match name {
+            "u8" => (Kind::Unsigned, 1),
+            "i8" => (Kind::Signed, 1),
+            "u16" => (Kind::Unsigned, 2),
+            "i16" => (Kind::Signed, 2),
+            "u32" => (Kind::Unsigned, 4),
+            "i32" => (Kind::Signed, 4),
+            "u64" => (Kind::Unsigned, 8),
+            "i64" => (Kind::Signed, 8),
+            "f32" => (Kind::Float, 4),
+            "f64" => (Kind::Float, 8),
+            _ => return Err(format!("`{s}` is not a field type")),
+        };
+
+        // Multi-byte types must say which way round they are
+        if size > 1 && name == s {
+            return Err(format!("`{s}` needs an le or be suffix"));
+        }
+
+        Ok(Self {
+            kind,
+            size,
+            little_endian,
+        })
+    }
+}
+
+/// A field of the payload, which becomes a channel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    field_type: FieldType,
+    /// Offset from the start of the payload
+    offset: usize,
+}
+
+/// Layout of a packet, as read from a layout file.
+///
+/// A packet is its sync bytes, an optional length field giving the length of the payload, the payload,
+/// and an optional checksum. Without a length field, the payload is as long as its fields. With framing,
+/// packets are split at frame delimiters instead of found by their sync bytes, which are then optional.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    framing: Option<Framing>,
+    sync: Vec<u8>,
+    length: Option<FieldType>,
+    pub fields: Vec<Field>,
+    /// Length of the payload without a length field
+    payload_len: usize,
+    checksum: Option<Checksum>,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    /// Parses a layout with one directive per line, where `#` starts a comment:
+    ///
+    /// ```text
+    /// framing cobs
+    /// sync AA 55
+    /// length u8
+    /// field temperature f32le
+    /// skip 2
+    /// field pressure u16be
+    /// crc crc16 le
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut layout = Self {
+            framing: None,
+            sync: Vec::new(),
+            length: None,
+            fields: Vec::new(),
+            payload_len: 0,
+            checksum: None,
+        };
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let error = |e: String| format!("line {}: {e}", i + 1);
+
+            match words.as_slice() {
+                [] => {}
+                ["framing", framing] => layout.framing = Some(framing.parse().map_err(error)?),
+                ["sync", bytes @ ..] if !bytes.is_empty() => {
+                    layout.sync = bytes
+                        .iter()
+                        .map(|b| {
+                            u8::from_str_radix(b.trim_start_matches("0x"), 16)
+                                .map_err(|_| error(format!("`{b}` is not a hex byte")))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                ["length", field_type] => {
+                    let field_type: FieldType = field_type.parse().map_err(error)?;
+                    if field_type.kind != Kind::Unsigned {
+                        return Err(error("the length must be an unsigned integer".to_string()));
+                    }
+                    layout.length = Some(field_type);
+                }
+                ["field", name, field_type] => {
+                    let field_type: FieldType = field_type.parse().map_err(error)?;
+                    layout.fields.push(Field {
+                        name: name.to_string(),
+                        field_type,
+                        offset: layout.payload_len,
+                    });
+                    layout.payload_len += field_type.size;
+                }
+                ["skip", bytes] => {
+                    layout.payload_len += bytes
+                        .parse::<usize>()
+                        .map_err(|_| error(format!("`{bytes}` is not a number of bytes")))?;
+                }
+                ["crc", checksum @ ..] => {
+                    layout.checksum = Some(checksum.join(" ").parse().map_err(error)?);
+                }
+                _ => {
+                    return Err(error(format!(
+                        "`{}` is not a layout directive",
+                        line.trim()
+                    )))
+                }
+            }
+        }
+
+        if layout.sync.is_empty() && layout.framing.is_none() {
+            return Err("layout needs sync bytes or framing".to_string());
+        }
+        if layout.fields.is_empty() {
+            return Err("layout has no fields".to_string());
+        }
+
+        Ok(layout)
+    }
+}
+
+impl Layout {
+    /// Decodes the fields of a packet from everything after its sync bytes, checking its checksum and
+    /// length.
+    fn decode_body(&self, body: &[u8]) -> Result<Vec<f64>, Corruption> {
+        let checksum_len = self.checksum.map_or(0, |c| c.size());
+        if body.len() < checksum_len {
+            return Err(Corruption::Truncated);
+        }
+
+        let (covered, checksum) = body.split_at(body.len() - checksum_len);
+        if let Some(kind) = self.checksum {
+            if !kind.matches(covered, checksum) {
+                return Err(Corruption::Checksum);
+            }
+        }
+
+        let payload = match self.length {
+            Some(length) => {
+                if covered.len() < length.size {
+                    return Err(Corruption::Truncated);
+                }
+                let (len, payload) = covered.split_at(length.size);
+                if length.decode(len) as usize != payload.len() {
+                    return Err(Corruption::Length);
+                }
+                payload
+            }
+            None => covered,
+        };
+
+        if payload.len() < self.payload_len {
+            return Err(Corruption::Truncated);
+        }
+
+        Ok(self
+            .fields
+            .iter()
+            .map(|f| {
+                f.field_type
+                    .decode(&payload[f.offset..f.offset + f.field_type.size])
+            })
+            .collect())
+    }
+}
+
+/// A decoded packet
+pub struct Packet {
+    /// Raw bytes of the whole packet
+    pub bytes: Vec<u8>,
+    /// Value of each field of the layout
+    pub values: Vec<f64>,
+}
+
+/// A packet that could not be decoded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Corruption {
+    /// The frame was not validly encoded, did not start with the sync bytes, or was too long
+    Framing,
+    /// The checksum did not match
+    Checksum,
+    /// The length field did not match the length of the frame
+    Length,
+    /// The payload was too short for the fields
+    Truncated,
+}
+
+/// Splits a byte stream into packets and decodes their fields
+pub struct Decoder {
+    layout: Layout,
+    /// Bytes read but not yet decoded
+    buffer: Vec<u8>,
+    /// Bytes at the start of the buffer that were claimed by a corrupted packet. Syncs among them are
+    /// likely false syncs in its payload, so their failures are not counted again.
+    suspect: usize,
+}
+
+impl Decoder {
+    pub fn new(layout: Layout) -> Self {
+        Self {
+            layout,
+            buffer: Vec::new(),
+            suspect: 0,
+        }
+    }
+
+    /// Adds bytes read from the stream, returning each whole packet, or why it was corrupted.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Result<Packet, Corruption>> {
+        self.buffer.extend_from_slice(bytes);
+
+        match self.layout.framing {
+            Some(framing) => self.split_frames(framing),
+            None => self.find_packets(),
+        }
+    }
+
+    /// Splits the buffer at frame delimiters, decoding each whole frame.
+    fn split_frames(&mut self, framing: Framing) -> Vec<Result<Packet, Corruption>> {
+        let layout = &self.layout;
+        let mut packets = Vec::new();
+
+        while let Some(end) = self.buffer.iter().position(|b| *b == framing.delimiter()) {
+            let frame: Vec<u8> = self.buffer.drain(..=end).take(end).collect();
+            // Delimiters may also start frames, leaving empty frames between them
+            if frame.is_empty() {
+                continue;
+            }
+
+            let packet = framing
+                .decode(&frame)
+                .and_then(|bytes| {
+                    let body = bytes.strip_prefix(layout.sync.as_slice())?.to_vec();
+                    Some((bytes, body))
+                })
+                .ok_or(Corruption::Framing)
+                .and_then(|(bytes, body)| {
+                    let values = layout.decode_body(&body)?;
+                    Ok(Packet { bytes, values })
+                });
+            packets.push(packet);
+        }
+
+        // A missing delimiter would otherwise grow the buffer forever
+        if self.buffer.len() > MAX_PAYLOAD_LEN * 2 {
+            self.buffer.clear();
+            packets.push(Err(Corruption::Framing));
+        }
+
+        packets
+    }
+
+    /// Finds packets by their sync bytes, decoding each whole packet.
+    fn find_packets(&mut self) -> Vec<Result<Packet, Corruption>> {
+        let layout = &self.layout;
+        let mut packets = Vec::new();
+
+        loop {
+            // Drop everything before the next sync, keeping a partial sync at the end
+            let start = match self
+                .buffer
+                .windows(layout.sync.len())
+                .position(|w| w == layout.sync)
+            {
+                Some(start) => start,
+                None => {
+                    let keep = (layout.sync.len() - 1).min(self.buffer.len());
+                    let dropped = self.buffer.len() - keep;
+                    self.buffer.drain(..dropped);
+                    self.suspect = self.suspect.saturating_sub(dropped);
+                    break;
+                }
+            };
+            self.buffer.drain(..start);
+            self.suspect = self.suspect.saturating_sub(start);
+
+            let header = layout.sync.len();
+            let (length_len, payload_len) = match layout.length {
+                Some(length) => {
+                    if self.buffer.len() < header + length.size {
+                        break;
+                    }
+                    let payload_len =
+                        length.decode(&self.buffer[header..header + length.size]) as usize;
+                    (length.size, payload_len)
+                }
+                None => (0, layout.payload_len),
+            };
+
+            // A length this long is a corrupted length or a false sync, so look for the next sync
+            if payload_len > MAX_PAYLOAD_LEN {
+                self.buffer.drain(..1);
+                self.suspect = self.suspect.saturating_sub(1);
+                continue;
+            }
+
+            let checksum_len = layout.checksum.map_or(0, |c| c.size());
+            let packet_len = header + length_len + payload_len + checksum_len;
+            if self.buffer.len() < packet_len {
+                break;
+            }
+
+            let packet = &self.buffer[..packet_len];
+            let decoded = layout.decode_body(&packet[header..]);
+            // Count one corruption per run of resyncs through a corrupted packet
+            if let (Err(corruption), 0) = (&decoded, self.suspect) {
+                packets.push(Err(*corruption));
+            }
+            match decoded {
+                Ok(values) => {
+                    packets.push(Ok(Packet {
+                        bytes: packet.to_vec(),
+                        values,
+                    }));
+                    self.buffer.drain(..packet_len);
+                    self.suspect = 0;
+                }
+                // This may have been a false sync, so only skip past its first byte
+                Err(Corruption::Checksum) => {
+                    self.suspect = self.suspect.max(packet_len) - 1;
+                    self.buffer.drain(..1);
+                }
+                Err(_) => {
+                    self.buffer.drain(..packet_len);
+                    self.suspect = self.suspect.saturating_sub(packet_len);
+                }
+            }
+        }
+
+        packets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(field: &str, bytes: &[u8]) -> f64 {
+        field.parse::<FieldType>().unwrap().decode(bytes)
+    }
+
+    #[test]
+    fn sign_extension() {
+        assert_eq!(decode("i8", &[0xFF]), -1.0);
+        assert_eq!(decode("u8", &[0xFF]), 255.0);
+        assert_eq!(decode("i16le", &[0x00, 0x80]), -32768.0);
+        assert_eq!(decode("i16be", &[0x80, 0x00]), -32768.0);
+        assert_eq!(decode("i16be", &[0x7F, 0xFF]), 32767.0);
+        assert_eq!(decode("u16be", &[0xFF, 0xFE]), 65534.0);
+        assert_eq!(decode("i32le", &[0xFE, 0xFF, 0xFF, 0xFF]), -2.0);
+        assert_eq!(decode("i64be", &[0xFF; 8]), -1.0);
+        assert_eq!(decode("f32le", &(-1.5f32).to_le_bytes()), -1.5);
+    }
+
+    #[test]
+    fn false_syncs_in_corrupted_payload() {
+        let layout: Layout = "sync AA 55\nfield x u16le\ncrc crc8".parse().unwrap();
+        let mut decoder = Decoder::new(layout);
+
+        // The payload of the corrupted packet holds the sync bytes, then a valid packet follows
+        let good = [0xAA, 0x55, 0x34, 0x12, crc8_of(&[0x34, 0x12])];
+        let mut stream = vec![0xAA, 0x55, 0xAA, 0x55, 0x00];
+        stream.extend(good);
+
+        let packets = decoder.push(&stream);
+        let corrupted = packets.iter().filter(|p| p.is_err()).count();
+        let values: Vec<Vec<f64>> = packets
+            .into_iter()
+            .filter_map(Result::ok)
+            .map(|p| p.values)
+            .collect();
+        assert_eq!(corrupted, 1);
+        assert_eq!(values, [vec![0x1234 as f64]]);
+    }
+
+    fn crc8_of(bytes: &[u8]) -> u8 {
+        (0..=255u8)
+            .find(|crc| Checksum::Crc8.matches(bytes, &[*crc]))
+            .unwrap()
+    }
+}
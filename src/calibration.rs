@@ -0,0 +1,116 @@
+//! Per-channel calibration of raw readings into real units
+
+use std::str::FromStr;
+
+/// A setting for a single channel, given on the command line as `CHANNEL=VALUE`.
+#[derive(Clone, Debug)]
+pub struct ChannelSetting<T> {
+    pub channel: usize,
+    pub value: T,
+}
+
+impl<T: FromStr> FromStr for ChannelSetting<T>
+where
+    T::Err: ToString,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (channel, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("`{s}` is not of the form CHANNEL=VALUE"))?;
+
+        Ok(Self {
+            channel: channel
+                .trim()
+                .parse()
+                .map_err(|_| format!("`{channel}` is not a channel index"))?,
+            value: value.trim().parse().map_err(|e: T::Err| e.to_string())?,
+        })
+    }
+}
+
+/// Piecewise-linear lookup table from raw readings to calibrated values
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LookupTable {
+    /// `(raw, calibrated)` points sorted by raw value
+    points: Vec<(f64, f64)>,
+}
+
+impl LookupTable {
+    /// Interpolates between the two nearest points, extrapolating from the end segments.
+    pub fn lookup(&self, raw: f64) -> f64 {
+        let points = &self.points;
+        if points.len() == 1 {
+            return points[0].1;
+        }
+
+        // Segment whose upper point is the first above raw, clamped to the end segments
+        let upper = points
+            .iter()
+            .position(|(x, _)| *x > raw)
+            .unwrap_or(points.len() - 1)
+            .clamp(1, points.len() - 1);
+        let ((x0, y0), (x1, y1)) = (points[upper - 1], points[upper]);
+
+        y0 + (raw - x0) * (y1 - y0) / (x1 - x0)
+    }
+}
+
+impl FromStr for LookupTable {
+    type Err = String;
+
+    /// Parses `RAW:VALUE` pairs separated by commas, such as `0:0,512:1.2,1023:3.3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut points = s
+            .split(',')
+            .map(|pair| {
+                let (x, y) = pair
+                    .split_once(':')
+                    .ok_or_else(|| format!("`{pair}` is not of the form RAW:VALUE"))?;
+                let parse = |v: &str| {
+                    v.trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|v| v.is_finite())
+                        .ok_or_else(|| format!("`{v}` is not a number"))
+                };
+                Ok((parse(x)?, parse(y)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if points.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err("lookup table has duplicate raw values".to_string());
+        }
+
+        Ok(Self { points })
+    }
+}
+
+/// Calibration applied to the raw readings of a channel as they are extracted.
+///
+/// The lookup table is applied first if set, followed by `value * scale + offset`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibration {
+    pub scale: f64,
+    pub offset: f64,
+    pub table: Option<LookupTable>,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            offset: 0.0,
+            table: None,
+        }
+    }
+}
+
+impl Calibration {
+    pub fn apply(&self, raw: f64) -> f64 {
+        let value = self.table.as_ref().map_or(raw, |t| t.lookup(raw));
+        value * self.scale + self.offset
+    }
+}
@@ -0,0 +1,166 @@
+//! Decoding of CBOR (RFC 8949) items
+
+use crate::record::{DecodeError, Item, Reader, MAX_DEPTH};
+
+/// Ends an item of indefinite length
+const BREAK: u8 = 0xFF;
+
+/// Argument of an item's initial byte
+enum Argument {
+    Value(u64),
+    Indefinite,
+}
+
+/// Reads the argument following the low 5 bits of an initial byte.
+fn read_argument(reader: &mut Reader, info: u8) -> Result<Argument, DecodeError> {
+    Ok(match info {
+        0..=23 => Argument::Value(info as u64),
+        24..=27 => Argument::Value(reader.uint(1 << (info - 24))?),
+        31 => Argument::Indefinite,
+        _ => return Err(DecodeError::Invalid),
+    })
+}
+
+/// Decodes one item, with `depth` arrays, maps or tags around it.
+pub fn decode(reader: &mut Reader, depth: usize) -> Result<Item, DecodeError> {
+    if depth > MAX_DEPTH {
+        return Err(DecodeError::Invalid);
+    }
+
+    let initial = reader.byte()?;
+    let (major, info) = (initial >> 5, initial & 0x1F);
+
+    if major == 7 {
+        return simple(reader, info);
+    }
+
+    let argument = read_argument(reader, info)?;
+    Ok(match (major, argument) {
+        (0, Argument::Value(n)) => Item::Int(n as i128),
+        (1, Argument::Value(n)) => Item::Int(-1 - n as i128),
+        (2, Argument::Value(n)) => Item::Bytes(reader.take(n as usize)?.to_vec()),
+        (3, Argument::Value(n)) => Item::Str(reader.str(n as usize)?),
+        (2 | 3, Argument::Indefinite) => {
+            // Chunks of the same major type until a break
+            let mut bytes = Vec::new();
+            while reader.peek()? != BREAK {
+                let chunk = reader.byte()?;
+                match (chunk >> 5, read_argument(reader, chunk & 0x1F)?) {
+                    (chunk_major, Argument::Value(n)) if chunk_major == major => {
+                        bytes.extend_from_slice(reader.take(n as usize)?)
+                    }
+                    _ => return Err(DecodeError::Invalid),
+                }
+            }
+            reader.byte()?;
+
+            if major == 2 {
+                Item::Bytes(bytes)
+            } else {
+                Item::Str(String::from_utf8(bytes).map_err(|_| DecodeError::Invalid)?)
+            }
+        }
+        (4, Argument::Value(n)) => Item::Array(
+            (0..n)
+                .map(|_| decode(reader, depth + 1))
+                .collect::<Result<_, _>>()?,
+        ),
+        (4, Argument::Indefinite) => {
+            let mut items = Vec::new();
+            while reader.peek()? != BREAK {
+                items.push(decode(reader, depth + 1)?);
+            }
+            reader.byte()?;
+            Item::Array(items)
+        }
+        (5, Argument::Value(n)) => Item::Map(
+            (0..n)
+                .map(|_| Ok((decode(reader, depth + 1)?, decode(reader, depth + 1)?)))
+                .collect::<Result<_, _>>()?,
+        ),
+        (5, Argument::Indefinite) => {
+            let mut entries = Vec::new();
+            while reader.peek()? != BREAK {
+                entries.push((decode(reader, depth + 1)?, decode(reader, depth + 1)?));
+            }
+            reader.byte()?;
+            Item::Map(entries)
+        }
+        // Tags, such as for timestamps, are dropped, keeping the item they tag
+        (6, Argument::Value(_)) => decode(reader, depth + 1)?,
+        _ => return Err(DecodeError::Invalid),
+    })
+}
+
+/// Decodes the simple values and floats of major type 7.
+fn simple(reader: &mut Reader, info: u8) -> Result<Item, DecodeError> {
+    Ok(match info {
+        20 => Item::Bool(false),
+        21 => Item::Bool(true),
+        22 | 23 => Item::Null,
+        25 => Item::Float(half_to_f64(reader.uint(2)? as u16)),
+        26 => Item::Float(f32::from_bits(reader.uint(4)? as u32) as f64),
+        27 => Item::Float(f64::from_bits(reader.uint(8)?)),
+        // Other simple values have no meaning here
+        0..=19 => Item::Null,
+        24 => {
+            reader.byte()?;
+            Item::Null
+        }
+        _ => return Err(DecodeError::Invalid),
+    })
+}
+
+/// Converts an IEEE 754 half precision float.
+fn half_to_f64(half: u16) -> f64 {
+    let exponent = (half >> 10) & 0x1F;
+    let mantissa = (half & 0x3FF) as f64;
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent as i32 - 15),
+    };
+
+    if half & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_bytes(bytes: &[u8]) -> Item {
+        decode(&mut Reader::new(bytes), 0).unwrap()
+    }
+
+    #[test]
+    fn half_floats() {
+        // Examples from RFC 8949 appendix A
+        assert_eq!(decode_bytes(&[0xF9, 0x00, 0x00]), Item::Float(0.0));
+        assert_eq!(decode_bytes(&[0xF9, 0x3C, 0x00]), Item::Float(1.0));
+        assert_eq!(decode_bytes(&[0xF9, 0x3E, 0x00]), Item::Float(1.5));
+        assert_eq!(decode_bytes(&[0xF9, 0x7B, 0xFF]), Item::Float(65504.0));
+        assert_eq!(
+            decode_bytes(&[0xF9, 0x00, 0x01]),
+            Item::Float(5.960464477539063e-8)
+        );
+        assert_eq!(
+            decode_bytes(&[0xF9, 0x04, 0x00]),
+            Item::Float(0.00006103515625)
+        );
+        assert_eq!(decode_bytes(&[0xF9, 0xC4, 0x00]), Item::Float(-4.0));
+        assert_eq!(
+            decode_bytes(&[0xF9, 0x7C, 0x00]),
+            Item::Float(f64::INFINITY)
+        );
+        assert_eq!(
+            decode_bytes(&[0xF9, 0xFC, 0x00]),
+            Item::Float(f64::NEG_INFINITY)
+        );
+        assert!(matches!(decode_bytes(&[0xF9, 0x7E, 0x00]), Item::Float(f) if f.is_nan()));
+    }
+}
@@ -1,26 +1,278 @@
 //! Command line arguments
 
-use std::path::{PathBuf};
+use crate::calibration::{ChannelSetting, LookupTable};
+use crate::colormap::Colormap;
+use crate::derive::Alignment;
+use crate::dsp::{Magnitude, Window};
+use crate::filter::{FilterDisplay, FilterSpec};
+use crate::logic::StateNames;
+use crate::nmea::NmeaField;
+use crate::number::{IntegerFormat, NumberFormat};
+use crate::record::{KeyPath, RecordFormat};
+use crate::transform::TransformSpec;
 use clap::Parser;
 use clap_verbosity_flag::Verbosity;
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
-#[command(long_about = "cliplot will live plot data piped into stdin. It can plot multiple channels, parse data with regex, save data to a CSV, and more.")]
+#[command(
+    long_about = "cliplot will live plot data piped into stdin. It can plot multiple channels, parse data with regex, save data to a CSV, and more. Stdin is read until it closes, which the status bar shows; it cannot reconnect, so restart cliplot to read from a new source."
+)]
 pub struct Args {
     /// Regex strings to parse each channel with. If this is not specified, then a single channel
-    /// that parses for `$float$` will be used.
+    /// that parses for `$float$` will be used, where the float may have an exponent such as `1.5e-3`.
     ///
     /// Each regex should be unambiguous from the others, and contain one capture group that contains
     /// a string convertable to a f64. Deliminators (such as the `$` above) are necessary to avoid
     /// numbers being cut across buffer breaks.
     #[arg(short, long)]
     pub regexes: Option<Vec<String>>,
+    /// Decodes stdin as binary packets described by a layout file, instead of matching text.
+    ///
+    /// Each field of the layout is a channel, numbered in the order given. The file has one directive
+    /// per line: an optional `framing cobs` or `framing slip` to split packets at frame delimiters,
+    /// `sync HEX...` for the bytes that start each packet (optional with framing), an optional
+    /// `length TYPE` for a field after the sync giving the payload length in bytes, `field NAME TYPE`
+    /// and `skip BYTES` for the payload, and an optional `crc KIND [le|be]` over the length and payload.
+    /// CRC kinds are `crc8` (SMBUS), `crc16` (CCITT-FALSE), `crc16-modbus` and `crc32`. Types are `u8`,
+    /// `i8`, or `u16`, `i16`, `u32`, `i32`, `u64`, `i64`, `f32` and `f64` followed by `le` or `be`. Text
+    /// after `#` is a comment. Corrupted packets are dropped and counted in the status bar.
+    #[arg(long, value_name = "FILE")]
+    pub layout: Option<PathBuf>,
+    /// Decodes stdin as a stream of MessagePack or CBOR records, such as maps, instead of matching text.
+    ///
+    /// Channels are read from the records with `--key`. Each record is shown in the console much like
+    /// JSON. Records that cannot be decoded are skipped a byte at a time and counted in the status bar.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub records: Option<RecordFormat>,
+    /// Adds a channel reading the item at a key path of each record, such as `imu.accel.0`.
+    ///
+    /// Keys are separated by `.`, and each names an entry of a map by its string or integer key, or an
+    /// index of an array. Key channels are numbered in the order given. Integers, floats and booleans are
+    /// read as numbers, and strings are parsed with the channel's number format. Integers are
+    /// reinterpreted with `--integer ...,signed=BITS`, and non-finite floats follow `non-finite` of
+    /// `--number-format`. Records without the key are skipped.
+    #[arg(long = "key", value_name = "PATH")]
+    pub keys: Vec<KeyPath>,
+    /// Adds a channel reading a field of NMEA 0183 sentences, such as `GGA.altitude`, and reads stdin as
+    /// sentences instead of matching regexes.
+    ///
+    /// Fields are `SENTENCE.NAME` or `SENTENCE.INDEX`, with indices counted from 1 after the sentence
+    /// type, such as `GSA.15` for PDOP. `GGA` matches sentences from any talker, such as `$GPGGA` and
+    /// `$GNGGA`, while `GPGGA` only matches one talker. Named fields are GGA `lat`, `lon`, `quality`,
+    /// `satellites`, `hdop`, `altitude` and `separation`; RMC `status` (1 if valid), `lat`, `lon`,
+    /// `speed` (knots), `course` and `variation`; and VTG `course`, `magnetic`, `knots` and `kmh`.
+    /// Latitudes and longitudes are in decimal degrees, negative to the south and west. NMEA channels
+    /// are numbered in the order given. Sentences with a missing or bad checksum are dropped and counted
+    /// in the status bar, and empty fields are skipped.
+    #[arg(long = "nmea", value_name = "SENTENCE.FIELD")]
+    pub nmea: Vec<NmeaField>,
+    /// Stamps readings with the UTC times of the NMEA sentences they were read from, instead of when
+    /// they were read.
+    ///
+    /// GGA, RMC and ZDA sentences carry times. Dates come from the latest RMC or ZDA sentence, or
+    /// otherwise today, and other sentences share the time of the latest timed sentence. Times are
+    /// plotted and logged relative to when cliplot started, so replayed logs are far in the past. Times
+    /// that go back are plotted in order, unless they are older than the plotted span, such as when a
+    /// replayed log restarts.
+    #[arg(long)]
+    pub nmea_time: bool,
+    /// Defines a virtual channel as an expression over other channels, such as `power = ch0 * ch1`.
+    ///
+    /// Channels are referred to as `ch<index>` or by the name of an earlier expression. Derived channels
+    /// are numbered after all input channels, including fan-out element channels and the field, key or
+    /// NMEA channels, in the order given, and are plotted and logged like any other channel. Supports
+    /// + - * / % ^, parentheses, pi, e, abs, sqrt, sin, cos, tan, exp, ln, log10, min and max.
+    #[arg(short, long = "expr", value_name = "NAME=EXPR")]
+    pub exprs: Vec<String>,
+    /// Adds a channel with the rate of matches of a regex per second, such as `GET /` for requests per
+    /// second from an access log.
+    ///
+    /// Counter channels are numbered after the regex channels, in the order given. The regex needs no
+    /// capture group.
+    #[arg(long = "count", value_name = "REGEX")]
+    pub counts: Vec<String>,
+    /// Length of the buckets that counter channels count matches over
+    #[arg(long, value_name = "MS", default_value_t = 1000, value_parser = clap::value_parser!(u64).range(10..))]
+    pub count_bucket: u64,
+    /// Adds a logic channel from a regex capturing a level or state, such as `gpio4=(\w+)`.
+    ///
+    /// Logic channels are drawn as lanes of a timing diagram under the chart instead of on it. Levels
+    /// may be 0/1, true/false, high/low or on/off, and other integers are drawn as labelled states.
+    /// Logic channels are numbered after the counter channels, in the order given.
+    #[arg(long = "logic", value_name = "REGEX")]
+    pub logic: Vec<String>,
+    /// Names the states of a logic channel, as `CHANNEL=NAME,NAME,...`. The channel is then drawn as
+    /// bands labelled with the state captured, and its readings are the index of the state.
+    #[arg(long, value_name = "CHANNEL=STATES")]
+    pub logic_states: Vec<ChannelSetting<StateNames>>,
+    /// Adds a state channel from a regex capturing the name of a state, such as `mode=(\w+)`.
+    ///
+    /// State channels are drawn as coloured bands labelled with the state in the timing diagram, and
+    /// their readings are logged to the CSV as text. State channels are numbered after the logic
+    /// channels, in the order given.
+    #[arg(long = "state", value_name = "REGEX")]
+    pub states: Vec<String>,
+    /// Adds an array channel from a regex capturing a whole array per match, such as `scan=\[(.*?)\]`.
+    ///
+    /// Values may be separated by commas, semicolons or whitespace. The latest frame of each array is
+    /// plotted against its index beside the chart. Array channels are numbered after the state channels,
+    /// in the order given.
+    #[arg(long = "array", value_name = "REGEX")]
+    pub arrays: Vec<String>,
+    /// Splits an array channel into a channel per element, as `CHANNEL=COUNT`, instead of plotting its
+    /// latest frame. Element channels are numbered after the array channels, in the order given.
+    #[arg(long = "fan-out", value_name = "CHANNEL=COUNT")]
+    pub fan_outs: Vec<ChannelSetting<usize>>,
+    /// Regex that marks an event on the chart whenever it matches, such as `ERROR` or `state -> (\w+)`.
+    ///
+    /// Events draw a vertical marker at the time they match, labelled with the text of the first
+    /// capture group, or the whole match if there is none. They do not produce readings.
+    #[arg(long = "event", value_name = "REGEX")]
+    pub events: Vec<String>,
+    /// How readings of different channels are lined up to evaluate expressions
+    #[arg(long, value_enum, default_value_t = Alignment::Record)]
+    pub align: Alignment,
+    /// Adds a filtered copy of a channel, as `CHANNEL:KIND:PARAM`.
+    ///
+    /// Kinds are `ma:N` (moving average of N readings), `ema:WEIGHT` (exponential smoothing, weight in 0..=1),
+    /// `median:N`, `lowpass:HZ` and `highpass:HZ` (first order), and `biquad-lowpass:HZ[:Q]` and
+    /// `biquad-highpass:HZ[:Q]` (second order, Butterworth if Q is not given). Filtered channels are
+    /// numbered after any derived channels, in the order given.
+    #[arg(short, long = "filter", value_name = "CHANNEL:KIND:PARAM")]
+    pub filters: Vec<FilterSpec>,
+    /// Whether filtered channels are plotted over their raw channel, or replace it
+    #[arg(long, value_enum, default_value_t = FilterDisplay::Overlay)]
+    pub filter_display: FilterDisplay,
+    /// Also writes filtered channels to the CSV
+    #[arg(long)]
+    pub filter_csv: bool,
+    /// Multiplies the readings of a regex, field, key or NMEA channel by a factor, as `CHANNEL=SCALE`.
+    #[arg(long = "scale", value_name = "CHANNEL=SCALE")]
+    pub scales: Vec<ChannelSetting<f64>>,
+    /// Adds an offset to the readings of a regex, field, key or NMEA channel after scaling, as `CHANNEL=OFFSET`.
+    #[arg(long = "offset", value_name = "CHANNEL=OFFSET")]
+    pub offsets: Vec<ChannelSetting<f64>>,
+    /// Sets how the numbers of a regex or key channel are written, as `CHANNEL=OPTION,OPTION,...`.
+    ///
+    /// Options are `decimal-comma` (`3,14`), `thousands` (skip separators such as in `1,234.5` or
+    /// `1 234`), `si` (SI prefixes such as `4.7k`, `10µ` or `2M`) and `non-finite=keep` or
+    /// `non-finite=drop` for readings of `inf` and `nan`. Exponents such as `1.5e-3` are always accepted.
+    #[arg(long = "number-format", value_name = "CHANNEL=OPTIONS")]
+    pub number_formats: Vec<ChannelSetting<NumberFormat>>,
+    /// Parses a regex or key channel as integers, as `CHANNEL=RADIX[,signed=BITS]`, such as `0=hex,signed=16`.
+    ///
+    /// The radix is `auto` (from a `0x`, `0b` or `0o` prefix, otherwise decimal), `bin`, `oct`, `dec`
+    /// or `hex`, and prefixes are optional. With `signed=BITS`, unsigned values are reinterpreted as
+    /// two's complement of that many bits, so `0xFFFF` is -1 with 16 bits. Values that do not fit are
+    /// unparseable. Integer items of records are reinterpreted the same way, whatever the radix.
+    #[arg(long = "integer", value_name = "CHANNEL=FORMAT")]
+    pub integers: Vec<ChannelSetting<IntegerFormat>>,
+    /// Maps the raw readings of a regex, field, key or NMEA channel through a piecewise-linear table
+    /// before scaling, as `CHANNEL=RAW:VALUE,RAW:VALUE,...`. Readings outside the table are extrapolated.
+    #[arg(long = "lut", value_name = "CHANNEL=TABLE")]
+    pub luts: Vec<ChannelSetting<LookupTable>>,
+    /// Sets the unit of a channel, as `CHANNEL=UNIT`. Units are shown in the legend and y axis, and
+    /// are added as a column to the CSV.
+    #[arg(long = "unit", value_name = "CHANNEL=UNIT")]
+    pub units: Vec<ChannelSetting<String>>,
+    /// Sets a lower limit on a channel, as `CHANNEL=LIMIT`. Limits are drawn on the chart, readings
+    /// outside of them are highlighted, and the alarm actions run when a channel goes outside them.
+    /// Limits apply to the raw readings, so are not drawn while a channel is shown transformed.
+    #[arg(long, value_name = "CHANNEL=LIMIT")]
+    pub lower: Vec<ChannelSetting<f64>>,
+    /// Sets an upper limit on a channel, as `CHANNEL=LIMIT`.
+    #[arg(long, value_name = "CHANNEL=LIMIT")]
+    pub upper: Vec<ChannelSetting<f64>>,
+    /// Shell command to run whenever a channel goes outside its limits, or stalls.
+    ///
+    /// The command is given the environment variables CLIPLOT_ALARM (`limit` or `stall`), CLIPLOT_CHANNEL
+    /// and CLIPLOT_NAME. Limit alarms also get CLIPLOT_VALUE and CLIPLOT_LIMIT, and stall alarms get
+    /// CLIPLOT_STALL_MS.
+    #[arg(long, value_name = "COMMAND")]
+    pub alarm_command: Option<String>,
+    /// Prints to stderr whenever a channel goes outside its limits, or stalls
+    #[arg(long)]
+    pub alarm_stderr: bool,
+    /// Exits with this status when a channel goes outside its limits, after writing the CSV
+    #[arg(long, value_name = "STATUS", num_args = 0..=1, default_missing_value = "1")]
+    pub alarm_exit: Option<i32>,
+    /// Flags channels that have not received data for this many milliseconds.
+    ///
+    /// Only channels read from stdin are watched, not derived, filtered or transformed channels, nor
+    /// regex channels removed while running. Stalled channels are greyed in the legend and listed in a
+    /// banner, and the alarm command is run when a channel stalls.
+    #[arg(long, value_name = "MS")]
+    pub stall_timeout: Option<u64>,
+    /// Shows a channel as its `derivative` or running `integral` over time, as `CHANNEL:KIND`.
+    ///
+    /// Both use the time between readings in seconds, so an integral of amps is coulombs. Transforms only
+    /// affect what is displayed unless `--transform-csv` is set, and can also be changed or reset from the
+    /// gui.
+    #[arg(short, long = "transform", value_name = "CHANNEL:KIND")]
+    pub transforms: Vec<TransformSpec>,
+    /// Also writes the transforms given on the command line to the CSV, as hidden channels numbered after
+    /// the filtered channels.
+    ///
+    /// Resetting integrals from the gui also resets these, but changing a transform from the gui does not.
+    #[arg(long)]
+    pub transform_csv: bool,
     /// Writes read data into a CSV file at path if set.
     ///
     /// The CSV file will contain the timestamp of each reading in ms, followed by the data and finally the channel number.
     #[arg(long)]
     pub csv: Option<PathBuf>,
+    /// Displays a live spectrum of the channel at this index, computed by an FFT over the visible time domain.
+    ///
+    /// Readings are resampled to a uniform spacing before the FFT, so irregularly timed data is fine.
+    #[arg(long, value_name = "CHANNEL")]
+    pub fft: Option<usize>,
+    /// Window function applied before the FFT. This applies to both the spectrum and spectrogram.
+    #[arg(long, value_enum, default_value_t = Window::Hann)]
+    pub fft_window: Window,
+    /// Magnitude scale of the spectrum
+    #[arg(long, value_enum, default_value_t = Magnitude::Db)]
+    pub fft_scale: Magnitude,
+    /// Displays a scrolling spectrogram of the channel at this index, with time on the x axis,
+    /// frequency on the y axis and magnitude as color.
+    #[arg(long, value_name = "CHANNEL")]
+    pub spectrogram: Option<usize>,
+    /// Displays a scrolling waterfall of the array channel at this index, with one row per frame,
+    /// array indices on the x axis and value as color. Frames that come faster than the history
+    /// divided into 256 rows are pooled into one row, keeping the peak of each cell.
+    #[arg(long, value_name = "CHANNEL")]
+    pub waterfall: Option<usize>,
+    /// How much history the waterfall scrolls over, in ms
+    #[arg(long, value_name = "MS", default_value_t = 10_000)]
+    pub waterfall_history: u64,
+    /// Colormap used for heatmaps
+    #[arg(long, value_enum, default_value_t = Colormap::Viridis)]
+    pub colormap: Colormap,
+    /// How much history the spectrogram scrolls over, in ms
+    #[arg(long, value_name = "MS", default_value_t = 60_000)]
+    pub spectrogram_history: u64,
+    /// Time between each spectrogram column, in ms
+    #[arg(long, value_name = "MS", default_value_t = 250, value_parser = clap::value_parser!(u64).range(1..))]
+    pub spectrogram_hop: u64,
+    /// Amount of data each spectrogram column is computed over, in ms. Can be at most 20000.
+    #[arg(long, value_name = "MS", default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..=20_000))]
+    pub spectrogram_span: u64,
+    /// Displays a live histogram of the visible readings of the channel at this index, with markers
+    /// for the mean and one standard deviation either side.
+    #[arg(long, value_name = "CHANNEL")]
+    pub histogram: Option<usize>,
+    /// Number of bins in the histogram. The range of the bins automatically fits the visible data.
+    #[arg(long, value_name = "BINS", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..=200))]
+    pub histogram_bins: u64,
+    /// Displays a table under the chart with statistics for each channel, over both the visible
+    /// time domain and the whole session.
+    #[arg(long)]
+    pub stats: bool,
+    /// Number of lines of raw input kept in the console, which shows the text matched by each regex in
+    /// its channel's color.
+    #[arg(long, value_name = "LINES", default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..=1000))]
+    pub console_lines: u64,
     #[command(flatten)]
     pub verbose: Verbosity,
 }
@@ -0,0 +1,112 @@
+//! Colormaps used to color magnitudes in heatmap views
+
+use clap::ValueEnum;
+use plotters::style::RGBColor;
+use std::fmt::{Display, Formatter};
+
+/// Colormap that maps a value in 0..=1 onto a color.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Magma,
+    Inferno,
+    Grayscale,
+    Jet,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 5] = [
+        Colormap::Viridis,
+        Colormap::Magma,
+        Colormap::Inferno,
+        Colormap::Grayscale,
+        Colormap::Jet,
+    ];
+
+    /// Evenly spaced control points of the colormap, from low to high
+    fn stops(&self) -> &'static [(u8, u8, u8)] {
+        match self {
+            Colormap::Viridis => &[
+                (68, 1, 84),
+                (72, 40, 120),
+                (62, 74, 137),
+                (49, 104, 142),
+                (38, 130, 142),
+                (31, 158, 137),
+                (53, 183, 121),
+                (110, 206, 88),
+                (181, 222, 43),
+                (253, 231, 37),
+            ],
+            Colormap::Magma => &[
+                (0, 0, 4),
+                (28, 16, 68),
+                (79, 18, 123),
+                (129, 37, 129),
+                (181, 54, 122),
+                (229, 80, 100),
+                (251, 135, 97),
+                (254, 194, 135),
+                (252, 253, 191),
+            ],
+            Colormap::Inferno => &[
+                (0, 0, 4),
+                (31, 12, 72),
+                (85, 15, 109),
+                (136, 34, 106),
+                (186, 54, 85),
+                (227, 89, 51),
+                (249, 140, 10),
+                (249, 201, 50),
+                (252, 255, 164),
+            ],
+            Colormap::Grayscale => &[(0, 0, 0), (255, 255, 255)],
+            Colormap::Jet => &[
+                (0, 0, 128),
+                (0, 0, 255),
+                (0, 255, 255),
+                (255, 255, 0),
+                (255, 0, 0),
+                (128, 0, 0),
+            ],
+        }
+    }
+
+    /// Gets the color for `t`, which is clamped to 0..=1.
+    pub fn color(&self, t: f64) -> RGBColor {
+        let stops = self.stops();
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+
+        // Linearly interpolate between the two closest stops
+        let pos = t * (stops.len() - 1) as f64;
+        let i = (pos as usize).min(stops.len() - 2);
+        let frac = pos - i as f64;
+        let (a, b) = (stops[i], stops[i + 1]);
+        let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * frac).round() as u8;
+
+        RGBColor(lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2))
+    }
+
+    /// Gets the color for `value` normalised between `low` and `high`.
+    pub fn color_in(&self, value: f64, low: f64, high: f64) -> RGBColor {
+        if high > low {
+            self.color((value - low) / (high - low))
+        } else {
+            self.color(0.0)
+        }
+    }
+}
+
+impl Display for Colormap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Colormap::Viridis => "viridis",
+            Colormap::Magma => "magma",
+            Colormap::Inferno => "inferno",
+            Colormap::Grayscale => "grayscale",
+            Colormap::Jet => "jet",
+        };
+        write!(f, "{name}")
+    }
+}
@@ -0,0 +1,178 @@
+//! Console showing the latest raw input, with the text matched by each channel's regex and each event
+//! pattern highlighted
+
+use crate::color_table::get_color_for_channels;
+use crate::extractor::Config;
+use crate::interface::Message;
+use iced::widget::{Button, Column, Row, Text};
+use iced::{Color, Element, Length};
+use regex::Regex;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Color of text matched by event patterns
+const EVENT_COLOR: Color = Color::from_rgb(0.45, 0.45, 0.45);
+
+/// Longest line shown, so a stream without newlines does not grow a single huge line
+const MAX_LINE_LEN: usize = 500;
+
+/// The latest lines of raw input, written by the extractor and read by the gui
+pub struct InputLines {
+    /// Lines ordered oldest first. The last line is the one still being read.
+    lines: Mutex<VecDeque<String>>,
+    /// Number of complete lines kept
+    capacity: usize,
+}
+
+impl InputLines {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Mutex::new(VecDeque::from([String::new()])),
+            capacity,
+        }
+    }
+
+    /// Adds raw input text.
+    pub fn push(&self, text: &str) {
+        let mut lines = self.lines.lock().unwrap();
+
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push_back(String::new());
+            }
+
+            let line = lines.back_mut().unwrap();
+            if line.len() < MAX_LINE_LEN {
+                line.push_str(part.trim_end_matches('\r'));
+            }
+        }
+
+        while lines.len() > self.capacity + 1 {
+            lines.pop_front();
+        }
+    }
+
+    /// Copies the lines, oldest first, dropping the line being read if nothing has been read into it yet.
+    pub fn snapshot(&self) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        let mut lines: Vec<String> = lines.iter().cloned().collect();
+
+        if lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+
+        lines
+    }
+}
+
+/// Collapsible panel displaying the raw input
+pub struct Console {
+    lines: Arc<InputLines>,
+    /// Regex and color of each regex channel
+    matchers: Vec<(Regex, Color)>,
+    expanded: bool,
+}
+
+impl Console {
+    pub fn new(config: &Config, capacity: usize) -> Self {
+        Self {
+            lines: Arc::new(InputLines::new(capacity)),
+            matchers: matchers(config),
+            expanded: false,
+        }
+    }
+
+    /// Updates the highlighted regexes after they are changed.
+    pub fn refresh(&mut self, config: &Config) {
+        self.matchers = matchers(config);
+    }
+
+    /// Lines to give to the extractor
+    pub fn lines(&self) -> Arc<InputLines> {
+        self.lines.clone()
+    }
+
+    pub fn toggle(&mut self) {
+        self.expanded = !self.expanded;
+    }
+
+    /// Splits a line into runs of text, each colored by the first channel that matched it.
+    fn highlight(&self, line: &str) -> Vec<(usize, usize, Option<Color>)> {
+        let mut colors: Vec<Option<Color>> = vec![None; line.len()];
+
+        for (regex, color) in &self.matchers {
+            for m in regex.find_iter(line) {
+                colors[m.range()]
+                    .iter_mut()
+                    .filter(|c| c.is_none())
+                    .for_each(|c| *c = Some(*color));
+            }
+        }
+
+        // Start of each run of the same color, only splitting on character boundaries
+        let mut starts: Vec<(usize, Option<Color>)> = Vec::new();
+        for (i, _) in line.char_indices() {
+            if starts.last().map(|(_, color)| *color) != Some(colors[i]) {
+                starts.push((i, colors[i]));
+            }
+        }
+
+        let runs = starts
+            .iter()
+            .enumerate()
+            .map(|(i, (start, color))| {
+                let end = starts.get(i + 1).map_or(line.len(), |next| next.0);
+                (*start, end, *color)
+            })
+            .collect();
+
+        runs
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let toggle = Button::new(if self.expanded {
+            "Hide raw input"
+        } else {
+            "Show raw input"
+        })
+        .on_press(Message::ToggleConsole);
+
+        let mut panel = Column::new().width(Length::Fill).spacing(2).push(toggle);
+        if !self.expanded {
+            return panel.into();
+        }
+
+        for line in self.lines.snapshot() {
+            let row =
+                self.highlight(&line)
+                    .into_iter()
+                    .fold(Row::new(), |row, (start, end, color)| {
+                        let text = Text::new(line[start..end].to_string()).size(14);
+                        row.push(match color {
+                            Some(color) => text.style(color),
+                            None => text,
+                        })
+                    });
+
+            panel = panel.push(row);
+        }
+
+        panel.into()
+    }
+}
+
+/// Regex and color of each channel read from stdin, followed by the event patterns
+fn matchers(config: &Config) -> Vec<(Regex, Color)> {
+    let colors = get_color_for_channels(config.channels.len());
+
+    config
+        .channels
+        .iter()
+        .zip(colors)
+        .filter_map(|(c, color)| {
+            let regex = c.source.regex()?;
+            Some((regex.clone(), Color::from_rgb8(color.0, color.1, color.2)))
+        })
+        .chain(config.events.iter().map(|e| (e.clone(), EVENT_COLOR)))
+        .collect()
+}
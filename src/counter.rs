@@ -0,0 +1,133 @@
+//! Channels that count regex matches per time bucket, such as requests or errors per second
+
+use crate::extractor::{Config, Data, Source, Value};
+use chrono::{DateTime, Duration, Utc};
+
+/// Most empty buckets emitted after input resumes, so a long pause does not flood the chart
+const MAX_CATCH_UP: i64 = 100;
+
+/// A counter channel's current bucket
+struct Bucket {
+    channel: usize,
+    start: DateTime<Utc>,
+    matches: u64,
+}
+
+/// Runtime state for all counter channels
+pub struct Counting {
+    length: Duration,
+    buckets: Vec<Bucket>,
+}
+
+impl Counting {
+    pub fn new(config: &Config, start: DateTime<Utc>) -> Self {
+        let buckets = config
+            .channels
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| matches!(c.source, Source::Counter(_)))
+            .map(|(channel, _)| Bucket {
+                channel,
+                start,
+                matches: 0,
+            })
+            .collect();
+
+        Self {
+            length: Duration::milliseconds(config.count_bucket_ms as i64),
+            buckets,
+        }
+    }
+
+    /// Counts a match on a counter channel in its current bucket.
+    pub fn count(&mut self, channel: usize) {
+        if let Some(bucket) = self.buckets.iter_mut().find(|b| b.channel == channel) {
+            bucket.matches += 1;
+        }
+    }
+
+    /// Ends any buckets that finished before `now`, returning the rate of matches per second in each.
+    ///
+    /// This must be called before counting the matches read at `now`, so they land in the right bucket.
+    pub fn close_buckets(&mut self, now: DateTime<Utc>) -> Vec<Data> {
+        let mut readings = Vec::new();
+        let length_ms = self.length.num_milliseconds();
+        let seconds = length_ms as f64 / 1000.0;
+
+        for bucket in &mut self.buckets {
+            while now >= bucket.start + self.length {
+                bucket.start += self.length;
+                readings.push(Data {
+                    stamp: bucket.start,
+                    channel: bucket.channel,
+                    data: Value::Number(bucket.matches as f64 / seconds),
+                });
+                bucket.matches = 0;
+
+                // Skip ahead over a long pause, leaving one empty bucket to show the rate dropped
+                let behind = (now - bucket.start).num_milliseconds() / length_ms;
+                if behind > MAX_CATCH_UP {
+                    bucket.start += self.length * (behind - 1) as i32;
+                }
+            }
+        }
+
+        readings
+    }
+
+    /// Ends all buckets when input closes at `now`, including the open one, whose rate is over the time it
+    /// was open.
+    pub fn flush(&mut self, now: DateTime<Utc>) -> Vec<Data> {
+        let mut readings = self.close_buckets(now);
+
+        for bucket in &self.buckets {
+            let seconds = (now - bucket.start).num_milliseconds() as f64 / 1000.0;
+            if seconds > 0.0 {
+                readings.push(Data {
+                    stamp: now,
+                    channel: bucket.channel,
+                    data: Value::Number(bucket.matches as f64 / seconds),
+                });
+            }
+        }
+
+        readings
+    }
+
+    /// How long input may pause before buckets are ended without it, so counts keep falling while it is idle
+    pub fn idle_timeout(&self) -> std::time::Duration {
+        let length_ms = self.length.num_milliseconds().clamp(1, 1000);
+        std::time::Duration::from_millis(length_ms as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractor::Channel;
+    use regex::Regex;
+
+    #[test]
+    fn flush_open_bucket() {
+        let config = Config {
+            channels: vec![Channel::new(
+                "get",
+                Source::Counter(Regex::new("GET").unwrap()),
+            )],
+            ..Config::default()
+        };
+        let start = Utc::now();
+        let mut counting = Counting::new(&config, start);
+
+        counting.count(0);
+        counting.count(0);
+        let idle = counting.close_buckets(start + Duration::milliseconds(2500));
+        let rates: Vec<Value> = idle.into_iter().map(|d| d.data).collect();
+        assert_eq!(rates, [Value::Number(2.0), Value::Number(0.0)]);
+
+        counting.count(0);
+        let last = counting.flush(start + Duration::milliseconds(2250));
+        let rates: Vec<Value> = last.into_iter().map(|d| d.data).collect();
+        assert_eq!(rates, [Value::Number(4.0)]);
+    }
+}
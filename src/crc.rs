@@ -0,0 +1,154 @@
+//! Checksums that validate binary packets
+
+use std::str::FromStr;
+
+/// Checksum at the end of a binary packet
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Checksum {
+    /// CRC-8/SMBUS: polynomial 0x07, initial value 0x00
+    Crc8,
+    /// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF, as used by many UART protocols
+    Crc16 { little_endian: bool },
+    /// CRC-16/MODBUS: reflected polynomial 0x8005, initial value 0xFFFF
+    Crc16Modbus { little_endian: bool },
+    /// CRC-32 as used by zlib and Ethernet: reflected polynomial 0x04C11DB7, initial value and final xor
+    /// 0xFFFFFFFF
+    Crc32 { little_endian: bool },
+}
+
+impl Checksum {
+    /// Size of the checksum in bytes
+    pub const fn size(&self) -> usize {
+        match self {
+            Checksum::Crc8 => 1,
+            Checksum::Crc16 { .. } | Checksum::Crc16Modbus { .. } => 2,
+            Checksum::Crc32 { .. } => 4,
+        }
+    }
+
+    /// Computes the checksum of some bytes.
+    fn compute(&self, bytes: &[u8]) -> u32 {
+        match self {
+            Checksum::Crc8 => crc8_smbus(bytes) as u32,
+            Checksum::Crc16 { .. } => crc16_ccitt(bytes) as u32,
+            Checksum::Crc16Modbus { .. } => crc16_modbus(bytes) as u32,
+            Checksum::Crc32 { .. } => crc32(bytes),
+        }
+    }
+
+    /// Whether a checksum read from a packet matches the bytes it covers
+    pub fn matches(&self, covered: &[u8], checksum: &[u8]) -> bool {
+        let crc = self.compute(covered);
+        let size = self.size();
+
+        match self {
+            Checksum::Crc8
+            | Checksum::Crc16 {
+                little_endian: true,
+            }
+            | Checksum::Crc16Modbus {
+                little_endian: true,
+            }
+            | Checksum::Crc32 {
+                little_endian: true,
+            } => checksum == &crc.to_le_bytes()[..size],
+            _ => checksum == &crc.to_be_bytes()[4 - size..],
+        }
+    }
+}
+
+impl FromStr for Checksum {
+    type Err = String;
+
+    /// Parses `crc8`, or `crc16`, `crc16-modbus` or `crc32` optionally followed by `le` (the default) or
+    /// `be`, such as `crc32 be`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let (kind, little_endian) = match words.as_slice() {
+            [kind] | [kind, "le"] => (*kind, true),
+            [kind, "be"] => (*kind, false),
+            _ => return Err(format!("`{s}` is not of the form KIND [le|be]")),
+        };
+
+        match kind {
+            "crc8" => Ok(Checksum::Crc8),
+            "crc16" => Ok(Checksum::Crc16 { little_endian }),
+            "crc16-modbus" => Ok(Checksum::Crc16Modbus { little_endian }),
+            "crc32" => Ok(Checksum::Crc32 { little_endian }),
+            _ => Err(format!("`{kind}` is not a crc")),
+        }
+    }
+}
+
+fn crc8_smbus(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn crc16_ccitt(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn crc16_modbus(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u16, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(0xFFFF_FFFF, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Standard check input of CRC catalogues
+    const CHECK: &[u8] = b"123456789";
+
+    #[test]
+    fn check_values() {
+        assert_eq!(crc8_smbus(CHECK), 0xF4);
+        assert_eq!(crc16_ccitt(CHECK), 0x29B1);
+        assert_eq!(crc16_modbus(CHECK), 0x4B37);
+        assert_eq!(crc32(CHECK), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn byte_order() {
+        let le: Checksum = "crc16 le".parse().unwrap();
+        let be: Checksum = "crc16 be".parse().unwrap();
+        assert!(le.matches(CHECK, &[0xB1, 0x29]));
+        assert!(be.matches(CHECK, &[0x29, 0xB1]));
+        assert!(!be.matches(CHECK, &[0xB1, 0x29]));
+    }
+}
@@ -0,0 +1,426 @@
+//! Virtual channels computed from expressions over other channels
+
+use crate::extractor::{Config, Data, Source, Value};
+use clap::ValueEnum;
+use std::fmt::{Display, Formatter};
+
+/// How readings from different channels are lined up to evaluate an expression.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Alignment {
+    /// Evaluate once every input has a new reading since the last evaluation
+    #[default]
+    Record,
+    /// Evaluate whenever any input has a new reading, holding the latest value of the others
+    Hold,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Function {
+    Abs,
+    Sqrt,
+    Sin,
+    Cos,
+    Tan,
+    Exp,
+    Ln,
+    Log10,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn parse(name: &str) -> Option<(Self, usize)> {
+        Some(match name {
+            "abs" => (Function::Abs, 1),
+            "sqrt" => (Function::Sqrt, 1),
+            "sin" => (Function::Sin, 1),
+            "cos" => (Function::Cos, 1),
+            "tan" => (Function::Tan, 1),
+            "exp" => (Function::Exp, 1),
+            "ln" => (Function::Ln, 1),
+            "log10" => (Function::Log10, 1),
+            "min" => (Function::Min, 2),
+            "max" => (Function::Max, 2),
+            _ => return None,
+        })
+    }
+}
+
+/// Parsed arithmetic expression over channels
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Number(f64),
+    /// Latest reading of the channel at this index
+    Channel(usize),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+impl Expr {
+    /// Parses an expression, resolving channel names to indices with `lookup`.
+    ///
+    /// Channels can be referred to by name or as `ch<index>`. Supports `+ - * / % ^`, parentheses,
+    /// the constants `pi` and `e`, and the functions abs, sqrt, sin, cos, tan, exp, ln, log10, min and max.
+    pub fn parse(src: &str, lookup: impl Fn(&str) -> Option<usize>) -> Result<Self, String> {
+        let tokens = tokenize(src)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            lookup: &lookup,
+        };
+
+        let expr = parser.expr()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(t) => Err(format!("unexpected `{}`", t)),
+        }
+    }
+
+    /// Evaluates the expression with the latest reading of each channel.
+    pub fn eval(&self, values: &[f64]) -> f64 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Channel(i) => values[*i],
+            Expr::Neg(e) => -e.eval(values),
+            Expr::Binary(op, l, r) => {
+                let (l, r) = (l.eval(values), r.eval(values));
+                match op {
+                    BinaryOp::Add => l + r,
+                    BinaryOp::Sub => l - r,
+                    BinaryOp::Mul => l * r,
+                    BinaryOp::Div => l / r,
+                    BinaryOp::Rem => l % r,
+                    BinaryOp::Pow => l.powf(r),
+                }
+            }
+            Expr::Call(f, args) => {
+                let a = args[0].eval(values);
+                match f {
+                    Function::Abs => a.abs(),
+                    Function::Sqrt => a.sqrt(),
+                    Function::Sin => a.sin(),
+                    Function::Cos => a.cos(),
+                    Function::Tan => a.tan(),
+                    Function::Exp => a.exp(),
+                    Function::Ln => a.ln(),
+                    Function::Log10 => a.log10(),
+                    Function::Min => a.min(args[1].eval(values)),
+                    Function::Max => a.max(args[1].eval(values)),
+                }
+            }
+        }
+    }
+
+    /// Adds the index of every channel this expression reads to `out`.
+    pub fn inputs(&self, out: &mut Vec<usize>) {
+        match self {
+            Expr::Number(_) => {}
+            Expr::Channel(i) => {
+                if !out.contains(i) {
+                    out.push(*i)
+                }
+            }
+            Expr::Neg(e) => e.inputs(out),
+            Expr::Binary(_, l, r) => {
+                l.inputs(out);
+                r.inputs(out);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|a| a.inputs(out)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Symbol(char),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{n}"),
+            Token::Ident(s) => write!(f, "{s}"),
+            Token::Symbol(c) => write!(f, "{c}"),
+        }
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                // Allow exponents such as 1e-3
+                let exp_sign = (c == '-' || c == '+') && src[..i].ends_with(['e', 'E']);
+                if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exp_sign {
+                    end = i + c.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let num = &src[start..end];
+            tokens.push(Token::Number(
+                num.parse().map_err(|_| format!("invalid number `{num}`"))?,
+            ));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' {
+                    end = i + c.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Ident(src[start..end].to_string()));
+        } else if "+-*/%^(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected character `{c}`"));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser over tokens
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    lookup: &'a dyn Fn(&str) -> Option<usize>,
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.tokens.get(self.pos) == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(format!("expected `{symbol}`"))
+        }
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                BinaryOp::Add
+            } else if self.eat('-') {
+                BinaryOp::Sub
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+    }
+
+    /// term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                BinaryOp::Mul
+            } else if self.eat('/') {
+                BinaryOp::Div
+            } else if self.eat('%') {
+                BinaryOp::Rem
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    /// unary := '-' unary | power
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+
+    /// power := atom ('^' unary)?
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            Ok(Expr::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(self.unary()?),
+            ))
+        } else {
+            Ok(base)
+        }
+    }
+
+    /// atom := number | name | function '(' args ')' | '(' expr ')'
+    fn atom(&mut self) -> Result<Expr, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| "unexpected end of expression".to_string())?;
+        self.pos += 1;
+
+        match token {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Symbol('(') => {
+                let e = self.expr()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            Token::Ident(name) if self.eat('(') => {
+                let (f, arity) =
+                    Function::parse(&name).ok_or_else(|| format!("unknown function `{name}`"))?;
+
+                let mut args = vec![self.expr()?];
+                while self.eat(',') {
+                    args.push(self.expr()?);
+                }
+                self.expect(')')?;
+
+                if args.len() != arity {
+                    return Err(format!("`{name}` takes {arity} argument(s)"));
+                }
+                Ok(Expr::Call(f, args))
+            }
+            Token::Ident(name) => match name.as_str() {
+                "pi" => Ok(Expr::Number(std::f64::consts::PI)),
+                "e" => Ok(Expr::Number(std::f64::consts::E)),
+                _ => (self.lookup)(&name)
+                    .map(Expr::Channel)
+                    .ok_or_else(|| format!("unknown channel `{name}`")),
+            },
+            Token::Symbol(c) => Err(format!("unexpected `{c}`")),
+        }
+    }
+}
+
+/// Splits a `name = expression` definition, as given on the command line.
+pub fn split_definition(def: &str) -> Result<(&str, &str), String> {
+    let (name, expr) = def
+        .split_once('=')
+        .ok_or_else(|| format!("`{def}` is not of the form name = expression"))?;
+    let name = name.trim();
+
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(format!("`{name}` is not a valid channel name"));
+    }
+
+    Ok((name, expr.trim()))
+}
+
+/// Runtime state for evaluating derived channels as data arrives
+pub struct Derivation {
+    alignment: Alignment,
+    /// Latest reading of each channel
+    latest: Vec<Option<f64>>,
+    /// For each derived channel, its index, expression, inputs and which inputs are fresh
+    derived: Vec<(usize, Expr, Vec<usize>, Vec<bool>)>,
+}
+
+impl Derivation {
+    pub fn new(config: &Config) -> Self {
+        let derived = config
+            .channels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match &c.source {
+                Source::Derived(expr) => {
+                    let mut inputs = Vec::new();
+                    expr.inputs(&mut inputs);
+                    let fresh = vec![false; inputs.len()];
+                    Some((i, expr.clone(), inputs, fresh))
+                }
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            alignment: config.alignment,
+            latest: vec![None; config.channels.len()],
+            derived,
+        }
+    }
+
+    /// Computes derived readings from a batch of readings, appending them to the batch. Derived
+    /// channels may depend on each other, as long as they only depend on channels defined before them.
+    pub fn process(&mut self, batch: &mut Vec<Data>) {
+        if self.derived.is_empty() {
+            return;
+        }
+
+        // Derived readings are pushed onto the batch, so they can trigger later derived channels
+        let mut i = 0;
+        while i < batch.len() {
+            let (stamp, reading_channel) = (batch[i].stamp, batch[i].channel);
+            let data = batch[i].data.number();
+            i += 1;
+
+            // Text readings cannot be computed with
+            let data = match data {
+                Some(data) => data,
+                None => continue,
+            };
+            // Channels added while running cannot be inputs, so are not tracked
+            if let Some(latest) = self.latest.get_mut(reading_channel) {
+                *latest = Some(data);
+            }
+
+            for (channel, expr, inputs, fresh) in &mut self.derived {
+                let input = match inputs.iter().position(|c| *c == reading_channel) {
+                    Some(input) => input,
+                    None => continue,
+                };
+                fresh[input] = true;
+
+                let ready = match self.alignment {
+                    Alignment::Record => fresh.iter().all(|f| *f),
+                    Alignment::Hold => inputs.iter().all(|c| self.latest[*c].is_some()),
+                };
+                if !ready {
+                    continue;
+                }
+
+                let values: Vec<f64> = self.latest.iter().map(|v| v.unwrap_or(f64::NAN)).collect();
+                fresh.iter_mut().for_each(|f| *f = false);
+
+                batch.push(Data {
+                    stamp,
+                    channel: *channel,
+                    data: Value::Number(expr.eval(&values)),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Signal processing helpers for the frequency domain views
+
+use clap::ValueEnum;
+use std::f64::consts::PI;
+use std::fmt::{Display, Formatter};
+
+/// Largest FFT we will compute, to keep the gui responsive with large time domains.
+pub const MAX_FFT_LEN: usize = 4096;
+
+/// Smallest FFT we will compute. Anything less is not worth plotting.
+pub const MIN_FFT_LEN: usize = 8;
+
+/// Window function applied to samples before taking the FFT.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Window {
+    /// No windowing, ie. a rectangular window
+    Rectangular,
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    pub const ALL: [Window; 4] = [
+        Window::Rectangular,
+        Window::Hann,
+        Window::Hamming,
+        Window::Blackman,
+    ];
+
+    /// Gets the window coefficient for sample `i` of `n`.
+    fn coefficient(&self, i: usize, n: usize) -> f64 {
+        if n < 2 {
+            return 1.0;
+        }
+        let x = 2.0 * PI * i as f64 / (n - 1) as f64;
+
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * x.cos(),
+            Window::Hamming => 0.54 - 0.46 * x.cos(),
+            Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+        }
+    }
+}
+
+impl Display for Window {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Window::Rectangular => "rectangular",
+            Window::Hann => "hann",
+            Window::Hamming => "hamming",
+            Window::Blackman => "blackman",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Scale to display spectrum magnitudes in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Magnitude {
+    /// Amplitude in the units of the signal
+    Linear,
+    /// Amplitude in dB relative to 1 unit of the signal
+    #[default]
+    Db,
+}
+
+impl Magnitude {
+    pub const ALL: [Magnitude; 2] = [Magnitude::Linear, Magnitude::Db];
+
+    /// Converts a linear amplitude into this scale.
+    pub fn scale(&self, amplitude: f64) -> f64 {
+        match self {
+            Magnitude::Linear => amplitude,
+            // Clamp to avoid -inf on empty bins
+            Magnitude::Db => 20.0 * amplitude.max(1e-12).log10(),
+        }
+    }
+}
+
+impl Display for Magnitude {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Magnitude::Linear => write!(f, "linear"),
+            Magnitude::Db => write!(f, "dB"),
+        }
+    }
+}
+
+/// Linearly resamples `(seconds, value)` samples sorted by time into `n` uniformly spaced samples.
+///
+/// Returns the resampled values and the new sample period in seconds, or None if the samples do
+/// not span any time.
+pub fn resample_uniform(samples: &[(f64, f64)], n: usize) -> Option<(Vec<f64>, f64)> {
+    let (first, last) = (samples.first()?.0, samples.last()?.0);
+    let span = last - first;
+    if n < 2 || span <= 0.0 {
+        return None;
+    }
+
+    let dt = span / (n - 1) as f64;
+    let mut out = Vec::with_capacity(n);
+    let mut j = 0;
+
+    for i in 0..n {
+        let t = first + dt * i as f64;
+
+        // Advance to the segment containing t
+        while j + 2 < samples.len() && samples[j + 1].0 < t {
+            j += 1;
+        }
+
+        let (t0, v0) = samples[j];
+        let (t1, v1) = samples[(j + 1).min(samples.len() - 1)];
+
+        // Readings from the same chunk share a timestamp, so just take the newest of them
+        if t1 <= t0 {
+            out.push(v1);
+        } else {
+            let frac = ((t - t0) / (t1 - t0)).clamp(0.0, 1.0);
+            out.push(v0 + (v1 - v0) * frac);
+        }
+    }
+
+    Some((out, dt))
+}
+
+/// In-place iterative radix-2 FFT. The length of both slices must be the same power of two.
+pub fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    // Bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    // Butterflies
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        let (w_re, w_im) = (angle.cos(), angle.sin());
+
+        for start in (0..n).step_by(len) {
+            let (mut cur_re, mut cur_im) = (1.0, 0.0);
+
+            for k in 0..len / 2 {
+                let a = start + k;
+                let b = a + len / 2;
+
+                let t_re = re[b] * cur_re - im[b] * cur_im;
+                let t_im = re[b] * cur_im + im[b] * cur_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+
+                let next_re = cur_re * w_re - cur_im * w_im;
+                cur_im = cur_re * w_im + cur_im * w_re;
+                cur_re = next_re;
+            }
+        }
+
+        len <<= 1;
+    }
+}
+
+/// Computes the single sided amplitude spectrum of `(seconds, value)` samples sorted by time.
+///
+/// Samples are resampled to a uniform spacing and have their mean removed before windowing, so the
+/// DC bin does not swamp the rest of the plot. Returns `(frequency in Hz, amplitude)` for each bin
+/// in linear units, or None if there is not enough data.
+pub fn amplitude_spectrum(samples: &[(f64, f64)], window: Window) -> Option<Vec<(f64, f64)>> {
+    let n = samples
+        .len()
+        .next_power_of_two()
+        .clamp(MIN_FFT_LEN, MAX_FFT_LEN);
+    let (mut re, dt) = resample_uniform(samples, n)?;
+
+    let mean = re.iter().sum::<f64>() / n as f64;
+    let mut window_sum = 0.0;
+    for (i, v) in re.iter_mut().enumerate() {
+        let w = window.coefficient(i, n);
+        window_sum += w;
+        *v = (*v - mean) * w;
+    }
+
+    let mut im = vec![0.0; n];
+    fft(&mut re, &mut im);
+
+    // Scale so a sine of amplitude A shows as A, regardless of window
+    let bin_hz = 1.0 / (dt * n as f64);
+    Some(
+        (0..=n / 2)
+            .map(|k| {
+                let amp = (re[k] * re[k] + im[k] * im[k]).sqrt() * 2.0 / window_sum;
+                (k as f64 * bin_hz, amp)
+            })
+            .collect(),
+    )
+}
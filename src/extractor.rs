@@ -1,178 +1,895 @@
 //! Stdin data extractor runtime
 
+use crate::alarm::{AlarmActions, Alarms, Limits};
+use crate::array::{parse_array, FanOut};
+use crate::binary::{Decoder, Layout, Packet};
+use crate::calibration::Calibration;
+use crate::console::InputLines;
+use crate::counter::Counting;
+use crate::derive::{Alignment, Derivation, Expr};
 use crate::extractor::State::Closed;
+use crate::filter::{FilterKind, Filtering};
+use crate::logic::{parse_state, StateNames};
+use crate::nmea::{NmeaField, Sentence, SentenceClock};
+use crate::number::NumberFormat;
+use crate::record::{Item, KeyPath, RecordDecoder, RecordFormat};
+use crate::status::StreamStatus;
+use crate::transform::{Transform, Transforming};
 use crate::Message;
 use chrono::{DateTime, Utc};
 use csv::Writer;
 use iced::Subscription;
 use regex::Regex;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use tokio::io::{stdin, AsyncReadExt, Stdin};
 
+/// Value of a reading
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    /// Name of a state, from a state channel
+    Text(Arc<str>),
+    /// Frame of values, from an array channel
+    Array(Arc<[f64]>),
+}
+
+impl Value {
+    /// The value as a number, if it is one
+    pub fn number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Text(_) | Value::Array(_) => None,
+        }
+    }
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Value::Number(0.0)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{n}"),
+            Value::Text(s) => write!(f, "{s}"),
+            Value::Array(values) => {
+                let values: Vec<String> = values.iter().map(f64::to_string).collect();
+                write!(f, "[{}]", values.join(" "))
+            }
+        }
+    }
+}
+
 /// Data parsed from the CLI
-#[derive(Copy, Clone, Default, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct Data {
     /// The time the data was read from the CLI
     pub stamp: DateTime<Utc>,
     /// Channel the data is from. Starts at 0
     pub channel: usize,
     /// Reading parsed from regex
-    pub data: f64,
+    pub data: Value,
+}
+
+/// A labelled moment in time, from a match of an event pattern
+#[derive(Clone, Debug)]
+pub struct Event {
+    /// The time the event was read from the CLI
+    pub stamp: DateTime<Utc>,
+    /// Text of the first capture group, or the whole match if there is none
+    pub label: String,
+}
+
+/// Longest event label kept, so a greedy pattern does not cover the chart in text
+const MAX_LABEL_LEN: usize = 40;
+
+/// Whether a match reaches the end of the text read so far, where the next chunk could still extend it
+fn is_partial(matched: &regex::Match, text: &str) -> bool {
+    matched.end() == text.len()
+}
+
+/// Where the readings of a channel come from
+pub enum Source {
+    /// Parsed from stdin with a regex
+    Regex(Regex),
+    /// Rate of matches of a regex per second, counted over each bucket
+    Counter(Regex),
+    /// Logic level or named state parsed from stdin with a regex
+    Logic(Regex, StateNames),
+    /// Any text parsed from stdin with a regex, naming the current state
+    State(Regex),
+    /// Array of numbers parsed from stdin with a regex
+    Array(Regex),
+    /// Element at an index of the array channel at this index
+    Element(usize, usize),
+    /// Field at this index of the binary layout
+    Field(usize),
+    /// Item at a key path of each MessagePack or CBOR record
+    Key(KeyPath),
+    /// Field of each NMEA 0183 sentence of a type
+    Nmea(NmeaField),
+    /// Computed from the readings of other channels
+    Derived(Expr),
+    /// Filtered readings of the channel at this index
+    Filter(usize, FilterKind),
+    /// Derivative or integral of the channel at this index, only computed to write it to the CSV
+    Transform(usize, Transform),
+    /// A regex channel removed from the gui, kept so the indices of later channels do not change
+    Removed,
 }
 
-/// Extractor configuration. This configures the regex for each channel, and implicitly defines the
-/// number of channels via the number of regex matchers.
+impl Source {
+    /// Whether readings come from stdin, rather than from other channels
+    pub fn is_input(&self) -> bool {
+        matches!(
+            self,
+            Source::Regex(_)
+                | Source::Counter(_)
+                | Source::Logic(..)
+                | Source::State(_)
+                | Source::Array(_)
+                | Source::Field(_)
+                | Source::Key(_)
+                | Source::Nmea(_)
+        )
+    }
+
+    /// Regex matched against stdin, if the channel is read from stdin
+    pub fn regex(&self) -> Option<&Regex> {
+        match self {
+            Source::Regex(regex)
+            | Source::Counter(regex)
+            | Source::Logic(regex, _)
+            | Source::State(regex)
+            | Source::Array(regex) => Some(regex),
+            _ => None,
+        }
+    }
+}
+
+/// A single channel of data
+pub struct Channel {
+    /// Name displayed in the legend
+    pub name: String,
+    pub source: Source,
+    /// Hides the channel from the chart
+    pub hidden: bool,
+    /// Writes the channel to the CSV, if one is configured
+    pub logged: bool,
+    /// Unit of the readings, such as V
+    pub unit: Option<String>,
+    /// How the numbers captured by a regex channel are written
+    pub format: NumberFormat,
+    /// Calibration applied to raw readings. Only regex and field channels are calibrated.
+    pub calibration: Option<Calibration>,
+    /// Limits that trigger alarms when crossed
+    pub limits: Limits,
+}
+
+impl Channel {
+    /// Creates a visible and logged channel.
+    pub fn new(name: impl Into<String>, source: Source) -> Self {
+        Self {
+            name: name.into(),
+            source,
+            hidden: false,
+            logged: true,
+            unit: None,
+            format: NumberFormat::default(),
+            calibration: None,
+            limits: Limits::default(),
+        }
+    }
+
+    /// Name of the channel with its unit, if it has one
+    pub fn label(&self) -> String {
+        match &self.unit {
+            Some(unit) => format!("{} [{}]", self.name, unit),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Creates a regex channel named after its index.
+    pub fn regex(index: usize, regex: Regex) -> Self {
+        Self::new(format!("channel {}", index), Source::Regex(regex))
+    }
+}
+
+/// Extractor configuration. This configures the source of each channel, and implicitly defines the
+/// number of channels.
 ///
 /// # Assumptions
 /// - Regex matchers can do anything, but must have a single capture group who's matching text must be convertable to a float.
-/// Its suggested to use delimiters, else the number can get split over a buffer divide.
+///   Its suggested to use delimiters, else the number can get split over a buffer divide.
+/// - Derived, filtered and transformed channels only refer to channels before them.
 pub struct Config {
-    /// Regex expression for each channel, ordered by index
-    pub matchers: Vec<Regex>,
+    /// Each channel, ordered by index
+    pub channels: Vec<Channel>,
+    /// How derived channels line up their inputs
+    pub alignment: Alignment,
+    /// Length of the buckets counter channels count matches over
+    pub count_bucket_ms: u64,
+    /// Patterns that mark events on the chart instead of producing readings
+    pub events: Vec<Regex>,
+    /// Decodes stdin as binary packets with this layout instead of as text, if set
+    pub layout: Option<Layout>,
+    /// Decodes stdin as MessagePack or CBOR records instead of as text, if set
+    pub records: Option<RecordFormat>,
+    /// Reads stdin as NMEA 0183 sentences instead of matching regexes
+    pub nmea: bool,
+    /// Stamps readings of NMEA sentences with their own times instead of when they were read
+    pub nmea_time: bool,
     /// CSV file to write to, if set
     pub csv: Option<PathBuf>,
+    /// What to do when a channel crosses its limits
+    pub alarm: AlarmActions,
+    /// Times the gui has reset running integrals, so transformed channels in the CSV are reset too
+    pub integral_resets: u64,
+}
+
+impl Config {
+    /// Default regex, which matches a float deliminated by $, with an optional exponent.
+    pub fn default_regex() -> Regex {
+        Regex::new(r"\$([+-]?\d*\.?\d*(?:[eE][+-]?\d+)?)\$").unwrap()
+    }
+
+    /// Finds the index of a channel by name, or by `ch<index>`.
+    pub fn channel_index(&self, name: &str) -> Option<usize> {
+        self.channels
+            .iter()
+            .position(|c| c.name == name)
+            .or_else(|| {
+                name.strip_prefix("ch")
+                    .and_then(|i| i.parse().ok())
+                    .filter(|i| *i < self.channels.len())
+            })
+    }
+
+    /// Labels of each channel including units, ordered by index
+    pub fn channel_labels(&self) -> Vec<String> {
+        self.channels.iter().map(Channel::label).collect()
+    }
+
+    /// Whether stdin is text matched by regexes, rather than binary packets, records or NMEA sentences
+    pub fn matches_regexes(&self) -> bool {
+        self.layout.is_none() && self.records.is_none() && !self.nmea
+    }
+
+    /// Whether any channel written to the CSV has a unit
+    fn logs_units(&self) -> bool {
+        self.channels.iter().any(|c| c.logged && c.unit.is_some())
+    }
 }
 
 impl Default for Config {
     /// Crates a config with a single channel, where that single channel just matches a float deliminated
     /// by $.
     fn default() -> Self {
-        let matchers = vec![Regex::new(r"\$([+|-]?\d*\.?\d*)\$").unwrap()];
         Self {
-            matchers,
+            channels: vec![Channel::regex(0, Self::default_regex())],
+            alignment: Alignment::default(),
+            count_bucket_ms: 1000,
+            events: Vec::new(),
+            layout: None,
+            records: None,
+            nmea: false,
+            nmea_time: false,
             csv: None,
+            alarm: AlarmActions::default(),
+            integral_resets: 0,
+        }
+    }
+}
+
+/// Everything needed to extract data while stdin is open
+struct Worker {
+    stdin: Stdin,
+    /// Config, which regex channels may be added to or changed while extracting
+    config: Arc<RwLock<Config>>,
+    /// Text read but not yet matched past
+    working_str: String,
+    /// Decoder of binary packets, used instead of matching text if set
+    decoder: Option<Decoder>,
+    /// Decoder of records, used instead of matching text if set
+    records: Option<RecordDecoder>,
+    /// Whether text is read as NMEA sentences
+    nmea: bool,
+    /// Times NMEA sentences by their own times if set
+    clock: Option<SentenceClock>,
+    csv: Option<Writer<File>>,
+    /// Start time of extraction in unix epoch ms
+    start_time: i64,
+    counting: Counting,
+    fan_out: FanOut,
+    derivation: Derivation,
+    filtering: Filtering,
+    transforming: Transforming,
+    alarms: Alarms,
+    status: Arc<StreamStatus>,
+    /// Raw input shown in the console
+    lines: Arc<InputLines>,
+}
+
+impl Worker {
+    fn new(
+        shared_config: Arc<RwLock<Config>>,
+        status: Arc<StreamStatus>,
+        lines: Arc<InputLines>,
+    ) -> Self {
+        let config = shared_config.read().unwrap();
+
+        // Create CSV and write headers if configured
+        let csv = config.csv.clone().map(|path| {
+            let mut csv = csv::WriterBuilder::new()
+                .from_path(&path)
+                .expect("CSV path error!");
+
+            let mut headers = vec![
+                "Timestamp".to_string(),
+                "Data".to_string(),
+                "Channel".to_string(),
+            ];
+            // Only add units if used, to keep the format the same for existing users
+            if config.logs_units() {
+                headers.push("Unit".to_string());
+            }
+
+            csv.write_record(&headers).expect("CSV write error!");
+
+            csv
+        });
+
+        let start_time = Utc::now();
+        let counting = Counting::new(&config, start_time);
+        let decoder = config.layout.clone().map(Decoder::new);
+        let records = config.records.map(RecordDecoder::new);
+        let nmea = config.nmea;
+        let clock = config.nmea_time.then(SentenceClock::default);
+        let fan_out = FanOut::new(&config);
+        let derivation = Derivation::new(&config);
+        let filtering = Filtering::new(&config);
+        let transforming = Transforming::new(&config);
+        let alarms = Alarms::new(&config);
+        drop(config);
+
+        Self {
+            stdin: stdin(),
+            counting,
+            fan_out,
+            derivation,
+            filtering,
+            transforming,
+            alarms,
+            status,
+            lines,
+            config: shared_config,
+            working_str: String::new(),
+            decoder,
+            records,
+            nmea,
+            clock,
+            csv,
+            start_time: start_time.timestamp_millis(),
+        }
+    }
+
+    /// Matches each regex channel and event pattern against the working string, returning the readings
+    /// and events found.
+    fn match_regexes(&mut self, done_time: DateTime<Utc>) -> (Vec<Data>, Vec<Event>) {
+        let mut message = Vec::new();
+        let mut events = Vec::new();
+
+        let mut furthest_capture = -1isize;
+        let config = self.config.read().unwrap();
+
+        for (i, channel) in config.channels.iter().enumerate() {
+            let matcher = match &channel.source {
+                Source::Regex(matcher)
+                | Source::Logic(matcher, _)
+                | Source::State(matcher)
+                | Source::Array(matcher) => matcher,
+                Source::Counter(matcher) => {
+                    for matches in matcher.find_iter(&self.working_str) {
+                        furthest_capture = furthest_capture.max(matches.end() as isize);
+                        self.counting.count(i);
+                        self.status.add_match(i);
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            let mut captures = matcher.capture_locations();
+
+            for matches in matcher.find_iter(&self.working_str) {
+                // A logic state or state name at the end may still be cut short, such as `t` of `true`
+                if matches!(channel.source, Source::Logic(..) | Source::State(_))
+                    && is_partial(&matches, &self.working_str)
+                {
+                    continue;
+                }
+
+                // Keep track of the furthest offset to shrink working string
+                if matches.end() as isize > furthest_capture {
+                    furthest_capture = matches.end() as isize;
+                }
+
+                //Read capture at the found spot to avoid searching all over again
+                matcher.captures_read_at(&mut captures, &self.working_str, matches.start());
+                let bounds = captures.get(1).unwrap();
+
+                // Assume one capture group on each regex, with only a floating point number, logic state,
+                // state name or array in it
+                let text = &self.working_str[bounds.0..bounds.1];
+                let value = match &channel.source {
+                    Source::Logic(_, states) => parse_state(text, states).map(Value::Number),
+                    Source::State(_) => (!text.is_empty()).then(|| Value::Text(text.into())),
+                    Source::Array(_) => parse_array(text).map(Value::Array),
+                    _ => channel.format.parse(text).map(|data| {
+                        Value::Number(channel.calibration.as_ref().map_or(data, |c| c.apply(data)))
+                    }),
+                };
+
+                match value {
+                    Some(data) => {
+                        log::trace!("data: {} channel: {i}", data);
+                        self.status.add_match(i);
+                        message.push(Data {
+                            stamp: done_time,
+                            channel: i,
+                            data,
+                        });
+                    }
+                    None => {
+                        log::debug!("unparseable capture `{text}` channel: {i}");
+                        self.status.add_unparseable();
+                    }
+                }
+            }
+        }
+
+        for matcher in &config.events {
+            for captures in matcher.captures_iter(&self.working_str) {
+                let matches = captures.get(0).unwrap();
+                // Leave a match at the end buffered, so `state -> (\w+)` does not label `state -> i` as `i`
+                if is_partial(&matches, &self.working_str) {
+                    continue;
+                }
+                if matches.end() as isize > furthest_capture {
+                    furthest_capture = matches.end() as isize;
+                }
+
+                let label = captures.get(1).unwrap_or(matches).as_str();
+                log::info!("event: {label}");
+                events.push(Event {
+                    stamp: done_time,
+                    label: label.chars().take(MAX_LABEL_LEN).collect(),
+                });
+            }
+        }
+
+        // Remove working string data from before the furthest match, since it's impossible for us to miss
+        // a match since its already been checked. It's still possible for part of a match to be
+        // present after this last match, so keep it around until we match again.
+        if furthest_capture != -1 {
+            self.working_str.drain(0..furthest_capture as usize);
+        }
+
+        (message, events)
+    }
+
+    /// Decodes the packets completed by a chunk of binary input into readings of the field channels.
+    fn decode_packets(&mut self, chunk: &[u8], done_time: DateTime<Utc>) -> Vec<Data> {
+        let mut message = Vec::new();
+        let decoder = match &mut self.decoder {
+            Some(decoder) => decoder,
+            None => return message,
+        };
+        let config = self.config.read().unwrap();
+
+        for packet in decoder.push(chunk) {
+            let Packet { bytes, values } = match packet {
+                Ok(packet) => packet,
+                Err(corruption) => {
+                    log::debug!("corrupted packet: {corruption:?}");
+                    self.status.add_corrupted();
+                    continue;
+                }
+            };
+
+            self.status.add_record();
+
+            // Show each packet in the console as a line of hex
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02X}")).collect();
+            self.lines.push(&format!("{}\n", hex.join(" ")));
+
+            for (i, channel) in config.channels.iter().enumerate() {
+                if let Source::Field(field) = channel.source {
+                    let data = values[field];
+                    log::trace!("data: {data} channel: {i}");
+                    self.status.add_match(i);
+                    message.push(Data {
+                        stamp: done_time,
+                        channel: i,
+                        data: Value::Number(
+                            channel.calibration.as_ref().map_or(data, |c| c.apply(data)),
+                        ),
+                    });
+                }
+            }
+        }
+
+        message
+    }
+
+    /// Decodes the records completed by a chunk of input into readings of the key channels.
+    fn decode_records(&mut self, chunk: &[u8], done_time: DateTime<Utc>) -> Vec<Data> {
+        let mut message = Vec::new();
+        let records = match &mut self.records {
+            Some(records) => records,
+            None => return message,
+        };
+        let config = self.config.read().unwrap();
+
+        for record in records.push(chunk) {
+            let record = match record {
+                Some(record) => record,
+                None => {
+                    self.status.add_corrupted();
+                    continue;
+                }
+            };
+
+            self.status.add_record();
+
+            // Show each record in the console, which also helps find key paths
+            self.lines.push(&format!("{record}\n"));
+
+            for (i, channel) in config.channels.iter().enumerate() {
+                let path = match &channel.source {
+                    Source::Key(path) => path,
+                    _ => continue,
+                };
+                // Records may leave out keys, such as fields only sent when they change
+                let item = match record.get(path) {
+                    Some(item) => item,
+                    None => continue,
+                };
+
+                let data = match item {
+                    Item::Int(i) => channel.format.integer(*i),
+                    Item::Float(x) => channel.format.float(*x),
+                    Item::Bool(b) => Some(*b as u8 as f64),
+                    // Some firmware sends numbers as strings
+                    Item::Str(text) => channel.format.parse(text),
+                    _ => None,
+                };
+
+                match data {
+                    Some(data) => {
+                        log::trace!("data: {data} channel: {i}");
+                        self.status.add_match(i);
+                        message.push(Data {
+                            stamp: done_time,
+                            channel: i,
+                            data: Value::Number(
+                                channel.calibration.as_ref().map_or(data, |c| c.apply(data)),
+                            ),
+                        });
+                    }
+                    None => {
+                        log::debug!("{path} of a record is not a number: {item}");
+                        self.status.add_unparseable();
+                    }
+                }
+            }
+        }
+
+        message
+    }
+
+    /// Reads each whole line of the working string as an NMEA sentence into readings of the NMEA channels.
+    fn read_sentences(&mut self, done_time: DateTime<Utc>) -> Vec<Data> {
+        let mut message = Vec::new();
+        let config = self.config.read().unwrap();
+
+        while let Some(end) = self.working_str.find('\n') {
+            let line: String = self.working_str.drain(..=end).collect();
+            // Receivers also print banners and other text, which are not sentences
+            if !line.trim_start().starts_with(['$', '!']) {
+                continue;
+            }
+            let sentence = match Sentence::parse(&line) {
+                Some(sentence) => sentence,
+                None => {
+                    log::debug!("corrupted sentence: {}", line.trim());
+                    self.status.add_corrupted();
+                    continue;
+                }
+            };
+            let stamp = self
+                .clock
+                .as_mut()
+                .map_or(done_time, |clock| clock.stamp(&sentence, done_time));
+
+            for (i, channel) in config.channels.iter().enumerate() {
+                let field = match &channel.source {
+                    Source::Nmea(field) => field,
+                    _ => continue,
+                };
+                // Fields are empty when the receiver has no fix, which is not an error
+                let text = match field.text(&sentence) {
+                    Some(text) => text,
+                    None => continue,
+                };
+
+                match field.read(&sentence) {
+                    Some(data) => {
+                        log::trace!("data: {data} channel: {i}");
+                        self.status.add_match(i);
+                        message.push(Data {
+                            stamp,
+                            channel: i,
+                            data: Value::Number(
+                                channel.calibration.as_ref().map_or(data, |c| c.apply(data)),
+                            ),
+                        });
+                    }
+                    None => {
+                        log::debug!("{field} is not a number: {text}");
+                        self.status.add_unparseable();
+                    }
+                }
+            }
+        }
+
+        message
+    }
+
+    /// Writes readings to the CSV if configured, skipping channels that are not logged.
+    fn write_csv(&mut self, message: &[Data]) {
+        if let Some(ref mut csv) = self.csv {
+            let config = self.config.read().unwrap();
+            let units = config.logs_units();
+
+            message
+                .iter()
+                .filter(|m| config.channels[m.channel].logged)
+                .for_each(|m| {
+                    let mut record = vec![
+                        format!("{}", m.stamp.timestamp_millis() - self.start_time),
+                        format!("{}", m.data),
+                        format!("{}", m.channel),
+                    ];
+                    if units {
+                        record.push(config.channels[m.channel].unit.clone().unwrap_or_default());
+                    }
+
+                    csv.write_record(&record).expect("Failed to write to CSV!");
+                });
+        }
+    }
+
+    /// Write all data out to the csv before we die
+    fn close(self) {
+        if let Some(mut csv) = self.csv {
+            log::debug!("Flushing csv writer");
+            let _ = csv.flush();
+            drop(csv);
         }
     }
 }
 
 /// State machine for the extraction stream
 enum State {
-    Starting(Arc<Config>),
-    Working(Stdin, Arc<Config>, String, Box<Option<Writer<File>>>, i64),
+    Starting(Arc<RwLock<Config>>, Arc<StreamStatus>, Arc<InputLines>),
+    Working(Box<Worker>),
     Closed,
 }
 
-/// Subscription that extracts data from stdin using the configured Regex matchers.
-pub fn extract_channels(config: Arc<Config>) -> Subscription<Message> {
+/// Subscription that extracts data from stdin using the configured Regex matchers, counting what it reads
+/// in `status` and copying the raw text into `lines`. Changes to the regex channels in `config` take effect
+/// from the next chunk read.
+pub fn extract_channels(
+    config: Arc<RwLock<Config>>,
+    status: Arc<StreamStatus>,
+    lines: Arc<InputLines>,
+) -> Subscription<Message> {
     struct Pipe;
 
     iced::subscription::unfold(
         std::any::TypeId::of::<Pipe>(),
-        State::Starting(config),
+        State::Starting(config, status, lines),
         |state| async {
             match state {
-                State::Starting(arc_config) => {
-                    let stin = stdin();
-
-                    // Create CSV and write headers if configured
-                    let csv = arc_config.csv.clone().map(|path| {
-                        let mut csv = csv::WriterBuilder::new()
-                            .from_path(&path)
-                            .expect("CSV path error!");
-
-                        csv.write_record(&[
-                            "Timestamp".to_string(),
-                            "Data".to_string(),
-                            "Channel".to_string(),
-                        ])
-                        .expect("CSV write error!");
-
-                        csv
-                    });
-
-                    (
-                        None,
-                        State::Working(
-                            stin,
-                            arc_config,
-                            String::new(),
-                            Box::new(csv),
-                            Utc::now().timestamp_millis(),
-                        ),
-                    )
-                }
-                State::Working(mut stin, config, mut working_str, mut csv, start_time) => {
-                    // Read chunks from stdin, this needs to be small else it is actually much slower due to regex scaling with input size
+                State::Starting(arc_config, status, lines) => (
+                    None,
+                    State::Working(Box::new(Worker::new(arc_config, status, lines))),
+                ),
+                State::Working(mut worker) => {
+                    // Read chunks from stdin, this needs to be small else it is actually much slower due to regex scaling with input size.
+                    // Reads time out while stdin is idle so count buckets still end on time.
                     let mut buff = [0u8; 4];
-                    if stin.read_exact(&mut buff).await.is_err() {
-                        log::error!("Stdin Closed");
+                    let read = tokio::time::timeout(
+                        worker.counting.idle_timeout(),
+                        worker.stdin.read(&mut buff),
+                    )
+                    .await;
+                    let (len, closed) = match read {
+                        Ok(Ok(0)) | Ok(Err(_)) => (0, true),
+                        Ok(Ok(len)) => (len, false),
+                        Err(_) => (0, false),
+                    };
+                    let chunk = &buff[..len];
+
+                    let done_time = Utc::now();
+                    worker.status.add_input(chunk);
+
+                    // Batch all readings from each chunk into one message, ending count buckets first so
+                    // this chunk's matches are counted in the current bucket
+                    let mut message = if closed {
+                        worker.counting.flush(done_time)
+                    } else {
+                        worker.counting.close_buckets(done_time)
+                    };
+                    let (readings, events) = if chunk.is_empty() {
+                        (Vec::new(), Vec::new())
+                    } else if worker.decoder.is_some() {
+                        (worker.decode_packets(chunk, done_time), Vec::new())
+                    } else if worker.records.is_some() {
+                        (worker.decode_records(chunk, done_time), Vec::new())
+                    } else {
+                        // Extend working string
+                        worker.status.add_lines(chunk);
+                        let str = String::from_utf8_lossy(chunk);
+                        worker.working_str.push_str(&str);
+                        worker.lines.push(&str);
 
-                        // Write all data out to the csv before we die
-                        if let Some(mut csv) = *csv {
-                            log::debug!("Flushing csv writer");
-                            let _ = csv.flush();
-                            drop(csv);
+                        if worker.nmea {
+                            (worker.read_sentences(done_time), Vec::new())
+                        } else {
+                            worker.match_regexes(done_time)
                         }
+                    };
+                    message.extend(readings);
 
-                        // Signal stdin was closed to stop from freezing gui
-                        return (Some(Message::Closed), Closed);
-                    }
+                    // Compute virtual channels from what was just read
+                    worker.fan_out.process(&mut message);
+                    worker.derivation.process(&mut message);
+                    worker.filtering.process(&mut message);
+                    let resets = worker.config.read().unwrap().integral_resets;
+                    worker.transforming.process(&mut message, resets);
 
-                    let done_time = Utc::now();
+                    worker.write_csv(&message);
 
-                    // Extend working string
-                    let str = String::from_utf8_lossy(&buff);
-                    working_str.push_str(&str);
-
-                    // Batch all readings from each chunk into one message
-                    let mut message = Vec::new();
-
-                    let mut furthest_capture = -1isize;
-
-                    // Match on each channel
-                    for (i, matcher) in config.matchers.iter().enumerate() {
-                        let mut captures = matcher.capture_locations();
-
-                        for matches in matcher.find_iter(&working_str) {
-                            // Keep track of the furthest offset to shrink working string
-                            if matches.end() as isize > furthest_capture {
-                                furthest_capture = matches.end() as isize;
-                            }
-
-                            //Read capture at the found spot to avoid searching all over again
-                            matcher.captures_read_at(&mut captures, &working_str, matches.start());
-                            let bounds = captures.get(1).unwrap();
-
-                            // Assume one capture group on each regex, with only a floating point number in it
-                            if let Ok(data) = &working_str[bounds.0..bounds.1].parse::<f64>() {
-                                log::trace!("data: {} channel: {i}", *data);
-                                message.push(Data {
-                                    stamp: done_time,
-                                    channel: i,
-                                    data: *data,
-                                });
-                            }
-                        }
+                    if let Some(code) = worker.alarms.check(&message) {
+                        log::error!("Limit crossed, exiting with status {code}");
+                        worker.close();
+                        std::process::exit(code);
                     }
 
-                    // Remove working string data from before the furthest match, since it's impossible for us to miss
-                    // a match since its already been checked. It's still possible for part of a match to be
-                    // present after this last match, so keep it around until we match again.
-                    if furthest_capture != -1 {
-                        working_str.drain(0..furthest_capture as usize);
-                    }
+                    if closed {
+                        log::error!("Stdin Closed");
+                        worker.close();
 
-                    // Write to CSV if configured
-                    if let Some(ref mut csv) = *csv {
-                        message.iter().for_each(|m| {
-                            csv.write_record(&[
-                                format!("{}", m.stamp.timestamp_millis() - start_time),
-                                format!("{}", m.data),
-                                format!("{}", m.channel),
-                            ])
-                            .expect("Failed to write to CSV!");
-                        });
+                        // Send the last readings, then signal stdin was closed to stop from freezing gui
+                        return (Some(Message::Data(message, events)), Closed);
                     }
 
-                    (
-                        Some(Message::Data(message)),
-                        State::Working(stin, config, working_str, csv, start_time),
-                    )
+                    (Some(Message::Data(message, events)), State::Working(worker))
                 }
                 Closed => (Some(Message::Closed), Closed),
             }
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a worker with a config, reading from a stdin that tests do not use.
+    fn worker(config: Config) -> Worker {
+        let status = Arc::new(StreamStatus::new(config.channels.len()));
+        let lines = Arc::new(InputLines::new(10));
+        Worker::new(Arc::new(RwLock::new(config)), status, lines)
+    }
+
+    #[test]
+    fn event_label_split_over_chunks() {
+        let mut worker = worker(Config {
+            events: vec![Regex::new(r"state -> (\w+)").unwrap()],
+            ..Config::default()
+        });
+
+        worker.working_str.push_str("state -> i");
+        let (_, events) = worker.match_regexes(Utc::now());
+        assert!(events.is_empty());
+
+        worker.working_str.push_str("dle\n");
+        let (_, events) = worker.match_regexes(Utc::now());
+        let labels: Vec<&str> = events.iter().map(|e| e.label.as_str()).collect();
+        assert_eq!(labels, ["idle"]);
+    }
+
+    #[test]
+    fn logic_state_split_over_chunks() {
+        let regex = Regex::new(r"pin=(\w+)").unwrap();
+        let mut worker = worker(Config {
+            channels: vec![Channel::new(
+                "pin",
+                Source::Logic(regex, StateNames(Vec::new())),
+            )],
+            ..Config::default()
+        });
+
+        worker.working_str.push_str("pin=t");
+        let (readings, _) = worker.match_regexes(Utc::now());
+        assert!(readings.is_empty());
+
+        worker.working_str.push_str("rue\n");
+        let (readings, _) = worker.match_regexes(Utc::now());
+        let values: Vec<&Value> = readings.iter().map(|d| &d.data).collect();
+        assert_eq!(values, [&Value::Number(1.0)]);
+    }
+
+    #[test]
+    fn state_name_split_over_chunks() {
+        let regex = Regex::new(r"mode=(\w+)").unwrap();
+        let mut worker = worker(Config {
+            channels: vec![Channel::new("mode", Source::State(regex))],
+            ..Config::default()
+        });
+
+        worker.working_str.push_str("mode=R");
+        let (readings, _) = worker.match_regexes(Utc::now());
+        assert!(readings.is_empty());
+
+        worker.working_str.push_str("UN\n");
+        let (readings, _) = worker.match_regexes(Utc::now());
+        let values: Vec<&Value> = readings.iter().map(|d| &d.data).collect();
+        assert_eq!(values, [&Value::Text("RUN".into())]);
+    }
+
+    #[test]
+    fn record_items_follow_number_format() {
+        let mut register = Channel::new("register", Source::Key("register".parse().unwrap()));
+        register.format.integer = Some("dec,signed=16".parse().unwrap());
+        let mut level = Channel::new("level", Source::Key("level".parse().unwrap()));
+        level.format.non_finite = crate::number::NonFinite::Drop;
+        let mut worker = worker(Config {
+            channels: vec![register, level],
+            records: Some(RecordFormat::Msgpack),
+            ..Config::default()
+        });
+
+        // {"register": 0xFFFF, "level": NaN} followed by {"register": 7, "level": 1.5}
+        let mut bytes = vec![0x82, 0xA8];
+        bytes.extend(b"register");
+        bytes.extend([0xCD, 0xFF, 0xFF, 0xA5]);
+        bytes.extend(b"level");
+        bytes.push(0xCB);
+        bytes.extend(f64::NAN.to_be_bytes());
+        bytes.extend([0x82, 0xA8]);
+        bytes.extend(b"register");
+        bytes.extend([0x07, 0xA5]);
+        bytes.extend(b"level");
+        bytes.push(0xCB);
+        bytes.extend(1.5f64.to_be_bytes());
+
+        let readings = worker.decode_records(&bytes, Utc::now());
+        let values: Vec<(usize, &Value)> = readings.iter().map(|d| (d.channel, &d.data)).collect();
+        assert_eq!(
+            values,
+            [
+                (0, &Value::Number(-1.0)),
+                (0, &Value::Number(7.0)),
+                (1, &Value::Number(1.5))
+            ]
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Filters that smooth or shape the readings of a channel into a new channel
+
+use crate::extractor::{Config, Data, Source, Value};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// How filtered channels are shown relative to the channel they filter.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum FilterDisplay {
+    /// Plot the filtered trace on top of the raw trace
+    #[default]
+    Overlay,
+    /// Hide the raw trace, only plotting the filtered one
+    Replace,
+}
+
+/// Filter applied to a channel
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FilterKind {
+    /// Mean of the last n readings
+    MovingAverage(usize),
+    /// Exponential smoothing with the given weight on new readings
+    Exponential(f64),
+    /// Median of the last n readings
+    Median(usize),
+    /// First order low-pass with a cutoff in Hz
+    LowPass(f64),
+    /// First order high-pass with a cutoff in Hz
+    HighPass(f64),
+    /// Second order low-pass with a cutoff in Hz and Q
+    BiquadLowPass(f64, f64),
+    /// Second order high-pass with a cutoff in Hz and Q
+    BiquadHighPass(f64, f64),
+}
+
+impl Display for FilterKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterKind::MovingAverage(n) => write!(f, "ma {n}"),
+            FilterKind::Exponential(a) => write!(f, "ema {a}"),
+            FilterKind::Median(n) => write!(f, "median {n}"),
+            FilterKind::LowPass(hz) => write!(f, "lowpass {hz}Hz"),
+            FilterKind::HighPass(hz) => write!(f, "highpass {hz}Hz"),
+            FilterKind::BiquadLowPass(hz, q) => write!(f, "biquad lowpass {hz}Hz Q{q}"),
+            FilterKind::BiquadHighPass(hz, q) => write!(f, "biquad highpass {hz}Hz Q{q}"),
+        }
+    }
+}
+
+/// A filter on a channel, as given on the command line.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FilterSpec {
+    /// Index of the channel to filter
+    pub channel: usize,
+    pub kind: FilterKind,
+}
+
+impl FromStr for FilterSpec {
+    type Err = String;
+
+    /// Parses `CHANNEL:KIND[:PARAM[:PARAM]]`, such as `0:ma:5` or `1:biquad-lowpass:10:0.707`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+        if parts.len() < 3 {
+            return Err(format!("`{s}` is not of the form CHANNEL:KIND:PARAM"));
+        }
+
+        let channel = parts[0]
+            .parse()
+            .map_err(|_| format!("`{}` is not a channel index", parts[0]))?;
+        let param = |i: usize| -> Result<f64, String> {
+            parts
+                .get(i)
+                .ok_or_else(|| format!("`{}` is missing a parameter", parts[1]))?
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite() && *v > 0.0)
+                .ok_or_else(|| format!("`{}` is not a positive number", parts[i]))
+        };
+        let count = |i: usize| -> Result<usize, String> {
+            parts[i]
+                .parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("`{}` is not a positive integer", parts[i]))
+        };
+        // Butterworth response if no Q is given
+        let q = || {
+            if parts.len() > 3 {
+                param(3)
+            } else {
+                Ok(std::f64::consts::FRAC_1_SQRT_2)
+            }
+        };
+
+        let kind = match parts[1] {
+            "ma" => FilterKind::MovingAverage(count(2)?),
+            "ema" => {
+                let alpha = param(2)?;
+                if alpha > 1.0 {
+                    return Err("ema weight must be in 0..=1".to_string());
+                }
+                FilterKind::Exponential(alpha)
+            }
+            "median" => FilterKind::Median(count(2)?),
+            "lowpass" => FilterKind::LowPass(param(2)?),
+            "highpass" => FilterKind::HighPass(param(2)?),
+            "biquad-lowpass" => FilterKind::BiquadLowPass(param(2)?, q()?),
+            "biquad-highpass" => FilterKind::BiquadHighPass(param(2)?, q()?),
+            other => return Err(format!("unknown filter `{other}`")),
+        };
+
+        Ok(Self { channel, kind })
+    }
+}
+
+/// Estimates the sample period of a channel from the time spanned by its readings. Readings from the
+/// same chunk share a timestamp, so the time between individual readings is not usable directly.
+#[derive(Default)]
+struct SampleClock {
+    first: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>,
+    count: usize,
+}
+
+impl SampleClock {
+    fn tick(&mut self, stamp: DateTime<Utc>) {
+        self.first.get_or_insert(stamp);
+        self.last = Some(stamp);
+        self.count += 1;
+    }
+
+    /// Average seconds between readings, if known
+    fn period(&self) -> Option<f64> {
+        let span = (self.last? - self.first?).num_microseconds()? as f64 / 1e6;
+        (self.count > 1 && span > 0.0).then(|| span / (self.count - 1) as f64)
+    }
+}
+
+/// Running state of a single filter
+struct FilterState {
+    kind: FilterKind,
+    clock: SampleClock,
+    /// Recent inputs, newest last
+    inputs: VecDeque<f64>,
+    /// Previous outputs, newest first
+    outputs: [f64; 2],
+    started: bool,
+}
+
+impl FilterState {
+    fn new(kind: FilterKind) -> Self {
+        Self {
+            kind,
+            clock: SampleClock::default(),
+            inputs: VecDeque::new(),
+            outputs: [0.0; 2],
+            started: false,
+        }
+    }
+
+    /// Filters the next reading
+    fn next(&mut self, stamp: DateTime<Utc>, x: f64) -> f64 {
+        self.clock.tick(stamp);
+
+        let window = match self.kind {
+            FilterKind::MovingAverage(n) | FilterKind::Median(n) => n,
+            _ => 3,
+        };
+        self.inputs.push_back(x);
+        if self.inputs.len() > window {
+            self.inputs.pop_front();
+        }
+
+        // Prime recursive filters with the first reading to avoid a step from zero
+        if !self.started {
+            self.started = true;
+            let y = match self.kind {
+                FilterKind::HighPass(_) | FilterKind::BiquadHighPass(..) => 0.0,
+                _ => x,
+            };
+            self.outputs = [y; 2];
+            return y;
+        }
+
+        let y = match self.kind {
+            FilterKind::MovingAverage(_) => {
+                self.inputs.iter().sum::<f64>() / self.inputs.len() as f64
+            }
+            FilterKind::Median(_) => {
+                let mut sorted: Vec<f64> = self.inputs.iter().copied().collect();
+                sorted.sort_by(f64::total_cmp);
+                let mid = sorted.len() / 2;
+                if sorted.len() % 2 == 0 {
+                    (sorted[mid - 1] + sorted[mid]) / 2.0
+                } else {
+                    sorted[mid]
+                }
+            }
+            FilterKind::Exponential(alpha) => self.outputs[0] + alpha * (x - self.outputs[0]),
+            FilterKind::LowPass(hz) => match self.clock.period() {
+                Some(dt) => {
+                    let rc = 1.0 / (2.0 * PI * hz);
+                    self.outputs[0] + dt / (rc + dt) * (x - self.outputs[0])
+                }
+                None => x,
+            },
+            FilterKind::HighPass(hz) => match self.clock.period() {
+                Some(dt) => {
+                    let rc = 1.0 / (2.0 * PI * hz);
+                    let prev_x = self.inputs[self.inputs.len() - 2];
+                    rc / (rc + dt) * (self.outputs[0] + x - prev_x)
+                }
+                None => 0.0,
+            },
+            FilterKind::BiquadLowPass(hz, q) | FilterKind::BiquadHighPass(hz, q) => {
+                let fs = match self.clock.period() {
+                    Some(dt) => 1.0 / dt,
+                    None => return self.outputs[0],
+                };
+                // Cutoffs above nyquist cannot be realised, so pass readings through as is
+                if hz >= fs / 2.0 || self.inputs.len() < 3 {
+                    self.outputs = [x, self.outputs[0]];
+                    return x;
+                }
+
+                // Coefficients from the RBJ audio EQ cookbook
+                let w0 = 2.0 * PI * hz / fs;
+                let (sin, cos) = w0.sin_cos();
+                let alpha = sin / (2.0 * q);
+                let (b0, b1) = match self.kind {
+                    FilterKind::BiquadLowPass(..) => ((1.0 - cos) / 2.0, 1.0 - cos),
+                    _ => ((1.0 + cos) / 2.0, -(1.0 + cos)),
+                };
+                let (a0, a1, a2) = (1.0 + alpha, -2.0 * cos, 1.0 - alpha);
+
+                let (x1, x2) = (self.inputs[1], self.inputs[0]);
+                (b0 * x + b1 * x1 + b0 * x2 - a1 * self.outputs[0] - a2 * self.outputs[1]) / a0
+            }
+        };
+
+        self.outputs = [y, self.outputs[0]];
+        y
+    }
+}
+
+/// Runtime state for all filtered channels
+pub struct Filtering {
+    /// Output channel, input channel and state of each filter
+    filters: Vec<(usize, usize, FilterState)>,
+}
+
+impl Filtering {
+    pub fn new(config: &Config) -> Self {
+        let filters = config
+            .channels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match &c.source {
+                Source::Filter(input, kind) => Some((i, *input, FilterState::new(*kind))),
+                _ => None,
+            })
+            .collect();
+
+        Self { filters }
+    }
+
+    /// Filters a batch of readings, appending the filtered readings to the batch. Filtered readings
+    /// are themselves filtered if another filter is on that channel.
+    pub fn process(&mut self, batch: &mut Vec<Data>) {
+        if self.filters.is_empty() {
+            return;
+        }
+
+        let mut i = 0;
+        while i < batch.len() {
+            let (stamp, channel) = (batch[i].stamp, batch[i].channel);
+            let data = batch[i].data.number();
+            i += 1;
+
+            // Only numbers can be filtered
+            let data = match data {
+                Some(data) => data,
+                None => continue,
+            };
+
+            for (output, input, state) in &mut self.filters {
+                if *input == channel {
+                    batch.push(Data {
+                        stamp,
+                        channel: *output,
+                        data: Value::Number(state.next(stamp, data)),
+                    });
+                }
+            }
+        }
+    }
+}
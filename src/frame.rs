@@ -0,0 +1,167 @@
+//! Plot of the latest frame of each array channel, with the array index along the x axis
+
+use crate::color_table::get_color_for_channels;
+use crate::extractor::{Config, Data, Source, Value};
+use crate::interface::{Message, FONT_BOLD, FONT_REGULAR};
+use iced::widget::canvas::{Cache, Frame, Geometry};
+use iced::{Element, Length, Size};
+use plotters::prelude::ChartBuilder;
+use plotters::style::RGBColor;
+use plotters_iced::plotters_backend::DrawingBackend;
+use plotters_iced::{Chart, ChartWidget};
+use std::sync::Arc;
+
+/// An array channel and the last frame read from it
+struct Latest {
+    channel: usize,
+    name: String,
+    color: RGBColor,
+    values: Option<Arc<[f64]>>,
+}
+
+/// Widget that redraws the latest frame of each array channel in place
+pub struct FrameChart {
+    cache: Cache,
+    arrays: Vec<Latest>,
+}
+
+impl FrameChart {
+    /// Creates the frame plot, or None if every array channel is fanned out to element channels.
+    pub fn new(config: &Config) -> Option<Self> {
+        let colors = get_color_for_channels(config.channels.len());
+        let fanned_out = |array| {
+            config
+                .channels
+                .iter()
+                .any(|c| matches!(c.source, Source::Element(a, _) if a == array))
+        };
+
+        let arrays: Vec<Latest> = config
+            .channels
+            .iter()
+            .enumerate()
+            .filter(|(i, c)| matches!(c.source, Source::Array(_)) && !fanned_out(*i))
+            .map(|(i, c)| Latest {
+                channel: i,
+                name: c.label(),
+                color: colors[i],
+                values: None,
+            })
+            .collect();
+
+        (!arrays.is_empty()).then(|| Self {
+            cache: Cache::new(),
+            arrays,
+        })
+    }
+
+    /// Keeps the newest frame of each array in a batch of readings.
+    pub fn push(&mut self, data: &[Data]) {
+        for reading in data {
+            if let Value::Array(values) = &reading.data {
+                if let Some(latest) = self
+                    .arrays
+                    .iter_mut()
+                    .find(|a| a.channel == reading.channel)
+                {
+                    latest.values = Some(values.clone());
+                    self.cache.clear();
+                }
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        ChartWidget::new(self)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .resolve_font(|_, style| match style {
+                plotters_iced::plotters_backend::FontStyle::Bold => FONT_BOLD,
+                _ => FONT_REGULAR,
+            })
+            .into()
+    }
+}
+
+impl Chart<Message> for FrameChart {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut chart: ChartBuilder<DB>) {
+        use plotters::{prelude::*, style::Color};
+
+        let frames: Vec<(&Latest, &[f64])> = self
+            .arrays
+            .iter()
+            .filter_map(|a| Some((a, a.values.as_deref()?)))
+            .collect();
+
+        let len = frames.iter().map(|(_, v)| v.len()).max().unwrap_or(1);
+        let (low, high) = frames
+            .iter()
+            .flat_map(|(_, v)| v.iter())
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(*v), hi.max(*v))
+            });
+        // Flat or missing frames still get a visible range
+        let (low, high) = if high > low {
+            (low, high)
+        } else if low.is_finite() {
+            (low - 0.5, low + 0.5)
+        } else {
+            (0.0, 1.0)
+        };
+
+        let mut chart = chart
+            .x_label_area_size(28)
+            .y_label_area_size(28)
+            .margin(20)
+            .build_cartesian_2d(0..len.saturating_sub(1).max(1), low..high)
+            .expect("failed to build chart");
+
+        chart
+            .configure_mesh()
+            .bold_line_style(BLUE.mix(0.4))
+            .light_line_style(BLUE.mix(0.2))
+            .axis_style(ShapeStyle::from(BLUE.mix(0.80)).stroke_width(1))
+            .x_desc("Index")
+            .y_labels(10)
+            .y_label_style(
+                ("sans-serif", 15)
+                    .into_font()
+                    .color(&BLUE.mix(0.80))
+                    .transform(FontTransform::Rotate90),
+            )
+            .x_labels(10)
+            .x_label_style(("sans-serif", 15).into_font().color(&BLUE.mix(0.80)))
+            .draw()
+            .expect("failed to draw chart mesh");
+
+        for (array, values) in &frames {
+            let color = array.color;
+            chart
+                .draw_series(LineSeries::new(
+                    values.iter().copied().enumerate(),
+                    color.stroke_width(2),
+                ))
+                .expect("failed to draw frame")
+                .label(array.name.clone())
+                .legend(move |(x, y)| {
+                    PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2))
+                });
+        }
+
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .position(SeriesLabelPosition::UpperRight)
+            .draw()
+            .expect("Failed to draw legend!");
+    }
+
+    #[inline]
+    fn draw<F: Fn(&mut Frame)>(&self, bounds: Size, draw_fn: F) -> Geometry {
+        self.cache.draw(bounds, draw_fn)
+    }
+}
@@ -0,0 +1,146 @@
+//! Frame delimiting schemes for binary streams, which mark where each packet ends
+
+use std::str::FromStr;
+
+/// SLIP frame end
+const SLIP_END: u8 = 0xC0;
+/// SLIP escape, followed by one of the escaped bytes below
+const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+/// How packets are delimited in a binary stream
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Framing {
+    /// Consistent Overhead Byte Stuffing, where each frame ends with a zero byte
+    Cobs,
+    /// Serial Line Internet Protocol (RFC 1055), where each frame ends with 0xC0
+    Slip,
+}
+
+impl Framing {
+    /// Byte that ends each frame
+    pub const fn delimiter(&self) -> u8 {
+        match self {
+            Framing::Cobs => 0x00,
+            Framing::Slip => SLIP_END,
+        }
+    }
+
+    /// Decodes a frame without its delimiter, or None if it is not validly encoded.
+    pub fn decode(&self, frame: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Framing::Cobs => decode_cobs(frame),
+            Framing::Slip => decode_slip(frame),
+        }
+    }
+}
+
+impl FromStr for Framing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cobs" => Ok(Framing::Cobs),
+            "slip" => Ok(Framing::Slip),
+            _ => Err(format!("`{s}` is not a framing, use cobs or slip")),
+        }
+    }
+}
+
+/// Each block starts with a code giving the distance to the next zero, where 0xFF means a full block of
+/// 254 bytes with no zero after it.
+fn decode_cobs(frame: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(frame.len());
+    let mut i = 0;
+
+    while i < frame.len() {
+        let code = frame[i] as usize;
+        if code == 0 || i + code > frame.len() {
+            return None;
+        }
+
+        decoded.extend_from_slice(&frame[i + 1..i + code]);
+        i += code;
+        if code < 0xFF && i < frame.len() {
+            decoded.push(0);
+        }
+    }
+
+    Some(decoded)
+}
+
+fn decode_slip(frame: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(frame.len());
+    let mut bytes = frame.iter();
+
+    while let Some(byte) = bytes.next() {
+        decoded.push(match *byte {
+            SLIP_ESC => match bytes.next() {
+                Some(&SLIP_ESC_END) => SLIP_END,
+                Some(&SLIP_ESC_ESC) => SLIP_ESC,
+                _ => return None,
+            },
+            byte => byte,
+        });
+    }
+
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes bytes with COBS, without the delimiter.
+    fn encode_cobs(bytes: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![0];
+        let mut code = 0;
+
+        for byte in bytes {
+            if *byte == 0 {
+                encoded[code] = (encoded.len() - code) as u8;
+                code = encoded.len();
+                encoded.push(0);
+            } else {
+                encoded.push(*byte);
+                if encoded.len() - code == 0xFF {
+                    encoded[code] = 0xFF;
+                    code = encoded.len();
+                    encoded.push(0);
+                }
+            }
+        }
+        encoded[code] = (encoded.len() - code) as u8;
+
+        encoded
+    }
+
+    #[test]
+    fn cobs_round_trip() {
+        // A full block of 254 non-zero bytes, then a zero and a short block
+        let mut bytes: Vec<u8> = (1..=254).collect();
+        bytes.extend([0, 7, 0, 9]);
+
+        let encoded = encode_cobs(&bytes);
+        assert_eq!(encoded[0], 0xFF);
+        assert!(!encoded.contains(&0));
+        assert_eq!(Framing::Cobs.decode(&encoded), Some(bytes));
+    }
+
+    #[test]
+    fn cobs_invalid() {
+        assert_eq!(Framing::Cobs.decode(&[0x03, 0x11]), None);
+        assert_eq!(Framing::Cobs.decode(&[0x00, 0x11]), None);
+    }
+
+    #[test]
+    fn slip_escapes() {
+        let frame = [0x01, SLIP_ESC, SLIP_ESC_END, SLIP_ESC, SLIP_ESC_ESC, 0x02];
+        assert_eq!(
+            Framing::Slip.decode(&frame),
+            Some(vec![0x01, SLIP_END, SLIP_ESC, 0x02])
+        );
+        assert_eq!(Framing::Slip.decode(&[0x01, SLIP_ESC, 0x03]), None);
+    }
+}
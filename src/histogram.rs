@@ -0,0 +1,263 @@
+//! Live histogram of a single channel
+
+use crate::color_table::get_color_for_channels;
+use crate::interface::{Message, SignalChart, FONT_BOLD, FONT_REGULAR};
+use crate::stats::Summary;
+use iced::widget::{
+    canvas::Cache, canvas::Frame, canvas::Geometry, row, Column, PickList, Slider, Text,
+};
+use iced::{Element, Length, Size};
+use plotters::prelude::ChartBuilder;
+use plotters::style::RGBColor;
+use plotters_iced::plotters_backend::DrawingBackend;
+use plotters_iced::{Chart, ChartWidget};
+
+/// Most bins selectable from the gui
+pub const MAX_BINS: usize = 200;
+
+/// Settings for the histogram panel
+#[derive(Copy, Clone, Debug)]
+pub struct HistogramSettings {
+    /// Channel to bin
+    pub channel: usize,
+    /// Number of bins across the range of visible values
+    pub bins: usize,
+}
+
+/// Widget that displays a histogram of the visible readings of a channel
+pub struct HistogramChart {
+    cache: Cache,
+    /// Color for each channel
+    colors: Vec<RGBColor>,
+    settings: HistogramSettings,
+    /// Lower edge of the first bin
+    low: f64,
+    /// Width of each bin
+    bin_width: f64,
+    counts: Vec<usize>,
+    /// Mean and standard deviation of the binned readings, if there are any
+    mean: Option<f64>,
+    std_dev: Option<f64>,
+    /// Channels removed while running, which are no longer listed
+    removed: Vec<usize>,
+}
+
+impl HistogramChart {
+    pub fn new(num_channels: usize, mut settings: HistogramSettings) -> Self {
+        settings.bins = settings.bins.clamp(1, MAX_BINS);
+
+        Self {
+            cache: Cache::new(),
+            colors: get_color_for_channels(num_channels),
+            settings,
+            low: 0.0,
+            bin_width: 1.0,
+            counts: Vec::new(),
+            mean: None,
+            std_dev: None,
+            removed: Vec::new(),
+        }
+    }
+
+    /// Makes a channel added while running selectable.
+    pub fn add_channel(&mut self) {
+        self.colors = get_color_for_channels(self.colors.len() + 1);
+    }
+
+    /// Stops listing a channel removed while running, emptying the histogram if it was shown.
+    pub fn remove_channel(&mut self, channel: usize, signal: &SignalChart) {
+        self.removed.push(channel);
+        if channel == self.settings.channel {
+            self.update(signal);
+        }
+    }
+
+    pub const fn channel(&self) -> usize {
+        self.settings.channel
+    }
+
+    pub fn set_channel(&mut self, channel: usize, signal: &SignalChart) {
+        self.settings.channel = channel;
+        self.update(signal);
+    }
+
+    pub fn set_bins(&mut self, bins: usize, signal: &SignalChart) {
+        self.settings.bins = bins.clamp(1, MAX_BINS);
+        self.update(signal);
+    }
+
+    /// Rebins the visible readings of the signal chart, auto ranging to fit them.
+    pub fn update(&mut self, signal: &SignalChart) {
+        let samples = signal.visible_samples(self.settings.channel);
+        let values: Vec<f64> = samples
+            .iter()
+            .map(|(_, v)| *v)
+            .filter(|v| v.is_finite())
+            .collect();
+
+        self.counts = vec![0; self.settings.bins];
+        self.cache.clear();
+
+        // Nothing visible, so forget the previous range and statistics
+        if values.is_empty() {
+            self.low = 0.0;
+            self.bin_width = 1.0;
+            self.mean = None;
+            self.std_dev = None;
+            return;
+        }
+
+        let (low, high) = values
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(*v), hi.max(*v))
+            });
+        // Constant signals still get a visible bar
+        let (low, high) = if high > low {
+            (low, high)
+        } else {
+            (low - 0.5, high + 0.5)
+        };
+
+        self.low = low;
+        self.bin_width = (high - low) / self.settings.bins as f64;
+        for v in &values {
+            let bin = ((v - low) / self.bin_width) as usize;
+            self.counts[bin.min(self.settings.bins - 1)] += 1;
+        }
+
+        let summary = Summary::from_samples(&samples);
+        self.mean = summary.mean();
+        self.std_dev = summary.std_dev();
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let channels: Vec<usize> = (0..self.colors.len())
+            .filter(|c| !self.removed.contains(c))
+            .collect();
+
+        Column::new()
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .spacing(5)
+            .push(row![
+                Text::new("Channel: "),
+                PickList::new(
+                    channels,
+                    Some(self.settings.channel),
+                    Message::HistogramChannel
+                ),
+                Text::new(format!(" Bins: {} ", self.settings.bins)),
+                Slider::new(1..=MAX_BINS as i32, self.settings.bins as i32, |v| {
+                    Message::HistogramBins(v as usize)
+                })
+                .width(Length::Units(200)),
+                Text::new(format!(
+                    " Mean: {} σ: {}",
+                    format_statistic(self.mean),
+                    format_statistic(self.std_dev)
+                )),
+            ])
+            .push(ChartWidget::new(self).height(Length::Fill).resolve_font(
+                |_, style| match style {
+                    plotters_iced::plotters_backend::FontStyle::Bold => FONT_BOLD,
+                    _ => FONT_REGULAR,
+                },
+            ))
+            .into()
+    }
+}
+
+/// Formats a statistic for the panel header, or a dash if there is nothing to compute it from.
+fn format_statistic(value: Option<f64>) -> String {
+    value.map_or("-".to_string(), |v| format!("{v:.4}"))
+}
+
+impl Chart<Message> for HistogramChart {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut chart: ChartBuilder<DB>) {
+        use plotters::{prelude::*, style::Color};
+
+        let high = self.low + self.bin_width * self.counts.len().max(1) as f64;
+        let max_count = self.counts.iter().copied().max().unwrap_or(0).max(1);
+
+        let mut chart = chart
+            .x_label_area_size(28)
+            .y_label_area_size(28)
+            .margin(20)
+            .build_cartesian_2d(self.low..high, 0..max_count)
+            .expect("failed to build chart");
+
+        chart
+            .configure_mesh()
+            .bold_line_style(BLUE.mix(0.4))
+            .light_line_style(BLUE.mix(0.2))
+            .axis_style(ShapeStyle::from(BLUE.mix(0.80)).stroke_width(1))
+            .y_labels(10)
+            .y_label_style(
+                ("sans-serif", 15)
+                    .into_font()
+                    .color(&BLUE.mix(0.80))
+                    .transform(FontTransform::Rotate90),
+            )
+            .x_labels(10)
+            .x_label_style(
+                ("sans-serif", 15)
+                    .into_font()
+                    .color(&BLUE.mix(0.80))
+                    .transform(FontTransform::Rotate90),
+            )
+            .draw()
+            .expect("failed to draw chart mesh");
+
+        let (mean, std_dev) = match (self.mean, self.std_dev) {
+            (Some(mean), Some(std_dev)) if self.counts.iter().any(|c| *c > 0) => (mean, std_dev),
+            _ => return,
+        };
+
+        let color = self.colors[self.settings.channel];
+        chart
+            .draw_series(self.counts.iter().enumerate().map(|(i, count)| {
+                let left = self.low + self.bin_width * i as f64;
+                Rectangle::new(
+                    [(left, 0), (left + self.bin_width, *count)],
+                    color.mix(0.6).filled(),
+                )
+            }))
+            .expect("failed to draw histogram");
+
+        // Mark the mean and one standard deviation either side
+        chart
+            .draw_series(std::iter::once(PathElement::new(
+                vec![(mean, 0), (mean, max_count)],
+                BLACK.stroke_width(2),
+            )))
+            .expect("failed to draw mean")
+            .label(format!("mean {mean:.4}"))
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLACK.stroke_width(2)));
+
+        chart
+            .draw_series(
+                [mean - std_dev, mean + std_dev]
+                    .into_iter()
+                    .map(|x| PathElement::new(vec![(x, 0), (x, max_count)], RED.stroke_width(1))),
+            )
+            .expect("failed to draw standard deviation")
+            .label(format!("±σ {std_dev:.4}"))
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], RED));
+
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .position(SeriesLabelPosition::UpperRight)
+            .draw()
+            .expect("Failed to draw legend!");
+    }
+
+    #[inline]
+    fn draw<F: Fn(&mut Frame)>(&self, bounds: Size, draw_fn: F) -> Geometry {
+        self.cache.draw(bounds, draw_fn)
+    }
+}
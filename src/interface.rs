@@ -1,7 +1,22 @@
 //! iced based gui interface
 
+use crate::alarm::Limits;
 use crate::color_table::get_color_for_channels;
-use crate::extractor::{extract_channels, Config, Data};
+use crate::colormap::Colormap;
+use crate::console::Console;
+use crate::dsp::{Magnitude, Window};
+use crate::extractor::{extract_channels, Config, Data, Event, Value};
+use crate::frame::FrameChart;
+use crate::histogram::{HistogramChart, HistogramSettings};
+use crate::logic::LogicChart;
+use crate::regex_editor::RegexEditor;
+use crate::spectrogram::{SpectrogramChart, SpectrogramSettings};
+use crate::spectrum::{SpectrumChart, SpectrumSettings};
+use crate::stats::StatsPanel;
+use crate::status::StatusBar;
+use crate::transform::{Transform, TransformSpec, Transforms};
+use crate::watchdog::Watchdog;
+use crate::waterfall::{WaterfallChart, WaterfallSettings};
 use chrono::{DateTime, Utc};
 use iced::widget::{row, Slider, Text, TextInput};
 use iced::{
@@ -19,38 +34,121 @@ use plotters_iced::plotters_backend::DrawingBackend;
 use plotters_iced::{Chart, ChartWidget};
 use std::collections::VecDeque;
 use std::default::Default;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-const FONT_REGULAR: Font = Font::External {
+pub const FONT_REGULAR: Font = Font::External {
     name: "sans-serif-regular",
     bytes: include_bytes!("../fonts/notosans-regular.ttf"),
 };
 
-const FONT_BOLD: Font = Font::External {
+pub const FONT_BOLD: Font = Font::External {
     name: "sans-serif-bold",
     bytes: include_bytes!("../fonts/notosans-bold.ttf"),
 };
 
+/// Color of warnings
+pub const WARNING: iced::Color = iced::Color::from_rgb(0.8, 0.1, 0.1);
+
+/// Color of stalled channels in the legend
+const STALLED: RGBColor = RGBColor(160, 160, 160);
+
+/// How often views computed over the whole visible window are recomputed while data arrives
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Debug, Clone)]
 pub enum Message {
-    /// Data from stdin
-    Data(Vec<Data>),
+    /// Readings and events from stdin
+    Data(Vec<Data>, Vec<Event>),
     /// Stdin was closed
     Closed,
     /// Slider changed
     SliderChanged(u64),
+    /// Spectrum channel selected
+    SpectrumChannel(usize),
+    /// Spectrum window function selected
+    SpectrumWindow(Window),
+    /// Spectrum magnitude scale selected
+    SpectrumMagnitude(Magnitude),
+    /// Spectrogram channel selected
+    SpectrogramChannel(usize),
+    /// Spectrogram colormap selected
+    SpectrogramColormap(Colormap),
+    /// Waterfall array channel selected
+    WaterfallChannel(usize),
+    /// Waterfall colormap selected
+    WaterfallColormap(Colormap),
+    /// Histogram channel selected
+    HistogramChannel(usize),
+    /// Histogram bin count changed
+    HistogramBins(usize),
+    /// Channel selected to change the transform of
+    TransformChannel(usize),
+    /// Transform of the selected channel changed
+    TransformKind(Transform),
+    /// Reset button pressed for integrals
+    ResetIntegrals,
+    /// Time to check for stalled channels
+    WatchdogTick(Instant),
+    /// Time to update the input rate in the status bar
+    StatusTick(Instant),
+    /// Time to recompute the spectrum, histogram and statistics with the data read since the last tick
+    RefreshTick,
+    /// Raw input console shown or hidden
+    ToggleConsole,
+    /// Regex settings shown or hidden
+    ToggleRegexEditor,
+    /// Pattern of a regex channel edited in the settings
+    RegexEdited(usize, String),
+    /// Edited pattern of a regex channel applied
+    RegexApplied(usize),
+    /// Regex channel removed
+    RegexRemoved(usize),
+    /// Pattern of a new regex channel edited
+    NewRegexEdited(String),
+    /// New regex channel added
+    RegexAdded,
 }
 
 #[derive(Default)]
 pub struct Flags {
-    pub extractor_conf: Arc<Config>,
+    pub extractor_conf: Arc<RwLock<Config>>,
+    /// Displays a spectrum panel if set
+    pub spectrum: Option<SpectrumSettings>,
+    /// Displays a spectrogram panel if set
+    pub spectrogram: Option<SpectrogramSettings>,
+    /// Displays a waterfall panel if set
+    pub waterfall: Option<WaterfallSettings>,
+    /// Displays a histogram panel if set
+    pub histogram: Option<HistogramSettings>,
+    /// Displays a table of per-channel statistics if set
+    pub stats: bool,
+    /// Channels to initially show transformed
+    pub transforms: Vec<TransformSpec>,
+    /// Flags channels that go without data for this long if set
+    pub stall_timeout: Option<Duration>,
+    /// Lines of raw input kept in the console
+    pub console_lines: usize,
 }
 
 /// Application state
 pub struct State {
     chart: SignalChart,
-    extractor_conf: Arc<Config>,
+    frames: Option<FrameChart>,
+    logic: Option<LogicChart>,
+    spectrum: Option<SpectrumChart>,
+    spectrogram: Option<SpectrogramChart>,
+    waterfall: Option<WaterfallChart>,
+    histogram: Option<HistogramChart>,
+    stats: Option<StatsPanel>,
+    /// Views with data that arrived since they were last computed
+    stale: StaleViews,
+    transforms: Transforms,
+    watchdog: Option<Watchdog>,
+    status: StatusBar,
+    console: Console,
+    regex_editor: RegexEditor,
+    extractor_conf: Arc<RwLock<Config>>,
     stdin_closed: bool,
 }
 
@@ -61,12 +159,55 @@ impl Application for State {
     type Flags = Flags;
 
     fn new(flags: Self::Flags) -> (Self, Command<Self::Message>) {
+        let config = flags.extractor_conf.read().unwrap();
+        let names = config.channel_labels();
+        let num_channels = names.len();
+
+        let mut chart = SignalChart::new(&config, Utc::now().timestamp_millis());
+        let mut transforms = Transforms::new(names.clone());
+        for spec in flags.transforms {
+            transforms.set(spec.channel, spec.transform);
+            chart.reset_channel(
+                spec.channel,
+                transforms.name(spec.channel),
+                spec.transform != Transform::None,
+            );
+        }
+
+        let watchdog = flags
+            .stall_timeout
+            .map(|timeout| Watchdog::new(&config, timeout));
+        let status = StatusBar::new(&config);
+        let frames = FrameChart::new(&config);
+        let waterfall = flags
+            .waterfall
+            .map(|settings| WaterfallChart::new(&config, settings));
+        let logic = LogicChart::new(&config);
+        let console = Console::new(&config, flags.console_lines);
+        drop(config);
+
         (
             Self {
-                chart: SignalChart::new(
-                    flags.extractor_conf.matchers.len(),
-                    Utc::now().timestamp_millis(),
-                ),
+                chart,
+                frames,
+                logic,
+                spectrum: flags
+                    .spectrum
+                    .map(|settings| SpectrumChart::new(num_channels, settings)),
+                spectrogram: flags
+                    .spectrogram
+                    .map(|settings| SpectrogramChart::new(num_channels, settings)),
+                waterfall,
+                histogram: flags
+                    .histogram
+                    .map(|settings| HistogramChart::new(num_channels, settings)),
+                stats: flags.stats.then(|| StatsPanel::new(names)),
+                stale: StaleViews::default(),
+                transforms,
+                watchdog,
+                status,
+                regex_editor: RegexEditor::new(flags.extractor_conf.clone(), console.lines()),
+                console,
                 extractor_conf: flags.extractor_conf,
                 stdin_closed: false,
             },
@@ -80,21 +221,235 @@ impl Application for State {
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
-            Message::Data(data) => data.into_iter().for_each(|d| self.chart.push_data(d)),
-            Message::Closed => self.stdin_closed = true,
+            Message::Data(data, events) => {
+                if let Some(watchdog) = &mut self.watchdog {
+                    data.iter().for_each(|d| watchdog.feed(d.channel));
+                    self.chart.set_stalled(watchdog);
+                }
+
+                let data: Vec<Data> = data
+                    .into_iter()
+                    .filter_map(|d| self.transforms.apply(d))
+                    .collect();
+
+                // Only recompute views whose channel changed
+                let touched = |channel| data.iter().any(|d: &Data| d.channel == channel);
+                self.stale.spectrum |= self.spectrum.as_ref().is_some_and(|s| touched(s.channel()));
+                let spectrogram_dirty = self
+                    .spectrogram
+                    .as_ref()
+                    .is_some_and(|s| touched(s.channel()));
+                self.stale.histogram |= self
+                    .histogram
+                    .as_ref()
+                    .is_some_and(|h| touched(h.channel()));
+                self.stale.stats |= !data.is_empty();
+
+                if let Some(stats) = &mut self.stats {
+                    for d in &data {
+                        if let Some(value) = d.data.number() {
+                            stats.push(d.channel, self.chart.seconds_since_start(d.stamp), value);
+                        }
+                    }
+                }
+
+                if let Some(frames) = &mut self.frames {
+                    frames.push(&data);
+                }
+
+                if let Some(waterfall) = &mut self.waterfall {
+                    waterfall.push(&data, &self.chart);
+                }
+
+                data.into_iter().for_each(|d| self.chart.push_data(d));
+                events.into_iter().for_each(|e| self.chart.push_event(e));
+
+                // Lanes scroll with the chart, so are updated whatever channel changed
+                if let Some(logic) = &mut self.logic {
+                    logic.update(&self.chart);
+                }
+
+                if let (Some(spectrogram), true) = (&mut self.spectrogram, spectrogram_dirty) {
+                    spectrogram.update(&self.chart);
+                }
+            }
+            Message::Closed => {
+                self.stdin_closed = true;
+                // Ticks stop with stdin, so show the last data now
+                self.refresh_views();
+            }
             // Scale the time domain using the slider or text inputs
-            Message::SliderChanged(v) => self.chart.set_time_domain(v),
+            Message::SliderChanged(v) => {
+                self.chart.set_time_domain(v);
+
+                if let Some(logic) = &mut self.logic {
+                    logic.update(&self.chart);
+                }
+
+                if let Some(spectrum) = &mut self.spectrum {
+                    spectrum.update(&self.chart);
+                }
+
+                if let Some(histogram) = &mut self.histogram {
+                    histogram.update(&self.chart);
+                }
+
+                if let Some(stats) = &mut self.stats {
+                    stats.update(&self.chart);
+                }
+            }
+            Message::SpectrumChannel(c) => {
+                if let Some(spectrum) = &mut self.spectrum {
+                    spectrum.set_channel(c, &self.chart);
+                }
+            }
+            Message::SpectrumWindow(w) => {
+                if let Some(spectrum) = &mut self.spectrum {
+                    spectrum.set_window(w, &self.chart);
+                }
+            }
+            Message::SpectrumMagnitude(m) => {
+                if let Some(spectrum) = &mut self.spectrum {
+                    spectrum.set_magnitude(m);
+                }
+            }
+            Message::SpectrogramChannel(c) => {
+                if let Some(spectrogram) = &mut self.spectrogram {
+                    spectrogram.set_channel(c);
+                }
+            }
+            Message::SpectrogramColormap(c) => {
+                if let Some(spectrogram) = &mut self.spectrogram {
+                    spectrogram.set_colormap(c);
+                }
+            }
+            Message::WaterfallChannel(c) => {
+                if let Some(waterfall) = &mut self.waterfall {
+                    waterfall.set_channel(c);
+                }
+            }
+            Message::WaterfallColormap(c) => {
+                if let Some(waterfall) = &mut self.waterfall {
+                    waterfall.set_colormap(c);
+                }
+            }
+            Message::HistogramChannel(c) => {
+                if let Some(histogram) = &mut self.histogram {
+                    histogram.set_channel(c, &self.chart);
+                }
+            }
+            Message::HistogramBins(b) => {
+                if let Some(histogram) = &mut self.histogram {
+                    histogram.set_bins(b, &self.chart);
+                }
+            }
+            Message::TransformChannel(c) => self.transforms.select(c),
+            Message::TransformKind(t) => {
+                // Old readings are in different units, so start the channel over
+                let channel = self.transforms.selected();
+                self.transforms.set(channel, t);
+                self.chart.reset_channel(
+                    channel,
+                    self.transforms.name(channel),
+                    t != Transform::None,
+                );
+            }
+            Message::ResetIntegrals => {
+                self.transforms.reset_integrals();
+                self.extractor_conf.write().unwrap().integral_resets += 1;
+            }
+            Message::WatchdogTick(now) => {
+                if let Some(watchdog) = &mut self.watchdog {
+                    if watchdog.check(now) {
+                        self.chart.set_stalled(watchdog);
+                    }
+                }
+            }
+            Message::StatusTick(now) => self.status.tick(now),
+            Message::RefreshTick => self.refresh_views(),
+            Message::ToggleConsole => self.console.toggle(),
+            Message::ToggleRegexEditor => self.regex_editor.toggle(),
+            Message::RegexEdited(i, pattern) => self.regex_editor.set_pattern(i, pattern),
+            Message::NewRegexEdited(pattern) => self.regex_editor.set_new_pattern(pattern),
+            Message::RegexApplied(i) => {
+                if self.regex_editor.apply(i) {
+                    self.refresh_regexes();
+                }
+            }
+            Message::RegexRemoved(i) => {
+                let channel = self.regex_editor.remove(i);
+                self.remove_channel(channel);
+                self.refresh_regexes();
+            }
+            Message::RegexAdded => {
+                if let Some((_, name)) = self.regex_editor.add() {
+                    self.add_channel(name);
+                    self.refresh_regexes();
+                }
+            }
         }
         Command::none()
     }
 
     fn view(&self) -> Element<'_, Self::Message> {
-        let content = Column::new()
+        let mut content = Column::new()
             .spacing(20)
             .align_items(Alignment::Center)
             .width(Length::Fill)
-            .height(Length::Fill)
-            .push(self.chart.view())
+            .height(Length::Fill);
+
+        // Banner for when data stops, so a frozen input does not look like a flat signal
+        if self.stdin_closed {
+            content = content.push(Text::new("Input closed").size(24).style(WARNING));
+        } else if let Some(watchdog) = &self.watchdog {
+            let stalled = watchdog.stalled_names();
+            if !stalled.is_empty() {
+                content = content.push(
+                    Text::new(format!(
+                        "No data for over {}ms on: {}",
+                        watchdog.timeout().as_millis(),
+                        stalled.join(", ")
+                    ))
+                    .size(24)
+                    .style(WARNING),
+                );
+            }
+        }
+
+        // Latest array frames sit beside the time series, as they share no axis with it
+        let mut content = match &self.frames {
+            Some(frames) => content.push(row![self.chart.view(), frames.view()].spacing(10)),
+            None => content.push(self.chart.view()),
+        };
+
+        if let Some(logic) = &self.logic {
+            content = content.push(logic.view());
+        }
+
+        if let Some(stats) = &self.stats {
+            content = content.push(stats.view());
+        }
+
+        if let Some(spectrum) = &self.spectrum {
+            content = content.push(spectrum.view());
+        }
+
+        if let Some(spectrogram) = &self.spectrogram {
+            content = content.push(spectrogram.view());
+        }
+
+        if let Some(waterfall) = &self.waterfall {
+            content = content.push(waterfall.view());
+        }
+
+        if let Some(histogram) = &self.histogram {
+            content = content.push(histogram.view());
+        }
+
+        let content = content
+            .push(self.console.view())
+            .push(self.regex_editor.view())
+            .push(self.transforms.view())
             .push(row![
                 Text::new("Max interval: ").size(30),
                 TextInput::new(
@@ -121,7 +476,8 @@ impl Application for State {
                 10..=20000,
                 self.chart.get_time_domain() as i32,
                 |v| Message::SliderChanged(v as u64),
-            ));
+            ))
+            .push(self.status.view(self.stdin_closed));
 
         Container::new(content)
             .width(Length::Fill)
@@ -135,20 +491,130 @@ impl Application for State {
     fn subscription(&self) -> Subscription<Self::Message> {
         // Keep reading until stdin closes, then avoid freezing gui
         if self.stdin_closed {
-            Subscription::none()
-        } else {
-            extract_channels(self.extractor_conf.clone())
+            return Subscription::none();
+        }
+
+        let mut subscriptions = vec![
+            extract_channels(
+                self.extractor_conf.clone(),
+                self.status.status(),
+                self.console.lines(),
+            ),
+            iced::time::every(Duration::from_secs(1)).map(Message::StatusTick),
+        ];
+        if let Some(watchdog) = &self.watchdog {
+            subscriptions
+                .push(iced::time::every(watchdog.tick_interval()).map(Message::WatchdogTick));
+        }
+        if self.spectrum.is_some() || self.histogram.is_some() || self.stats.is_some() {
+            subscriptions.push(iced::time::every(REFRESH_INTERVAL).map(|_| Message::RefreshTick));
+        }
+
+        Subscription::batch(subscriptions)
+    }
+}
+
+impl State {
+    /// Grows every per-channel view to include a channel added after the last one.
+    fn add_channel(&mut self, name: String) {
+        self.chart.add_channel(name.clone());
+        self.transforms.add_channel(name.clone());
+
+        if let Some(stats) = &mut self.stats {
+            stats.add_channel(name.clone());
+        }
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.add_channel(name);
+        }
+        if let Some(spectrum) = &mut self.spectrum {
+            spectrum.add_channel();
+        }
+        if let Some(spectrogram) = &mut self.spectrogram {
+            spectrogram.add_channel();
+        }
+        if let Some(histogram) = &mut self.histogram {
+            histogram.add_channel();
+        }
+    }
+
+    /// Drops a channel removed while running from every per-channel view.
+    fn remove_channel(&mut self, channel: usize) {
+        self.chart.remove_channel(channel);
+        self.transforms.remove_channel(channel);
+
+        if let Some(stats) = &mut self.stats {
+            stats.remove_channel(channel);
+        }
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.remove_channel(channel);
+            self.chart.set_stalled(watchdog);
+        }
+        if let Some(spectrum) = &mut self.spectrum {
+            spectrum.remove_channel(channel, &self.chart);
+        }
+        if let Some(spectrogram) = &mut self.spectrogram {
+            spectrogram.remove_channel(channel);
+        }
+        if let Some(histogram) = &mut self.histogram {
+            histogram.remove_channel(channel, &self.chart);
         }
     }
+
+    /// Recomputes the views computed over the whole visible window that have new data. This is done on a
+    /// tick rather than for each batch of data, since batches arrive for every few bytes read.
+    fn refresh_views(&mut self) {
+        let stale = std::mem::take(&mut self.stale);
+
+        if let (Some(spectrum), true) = (&mut self.spectrum, stale.spectrum) {
+            spectrum.update(&self.chart);
+        }
+
+        if let (Some(histogram), true) = (&mut self.histogram, stale.histogram) {
+            histogram.update(&self.chart);
+        }
+
+        if let (Some(stats), true) = (&mut self.stats, stale.stats) {
+            stats.update(&self.chart);
+        }
+    }
+
+    /// Updates the views that show regexes after they change.
+    fn refresh_regexes(&mut self) {
+        let config = self.extractor_conf.read().unwrap();
+        self.status.refresh(&config);
+        self.console.refresh(&config);
+    }
+}
+
+/// Views computed over the whole visible window that have data they have not been computed with
+#[derive(Default)]
+struct StaleViews {
+    spectrum: bool,
+    histogram: bool,
+    stats: bool,
 }
 
 /// Widget that displays our chart
-struct SignalChart {
+pub struct SignalChart {
     cache: Cache,
     /// Color for each channel
     colors: Vec<RGBColor>,
+    /// Name of each channel
+    names: Vec<String>,
+    /// Channels that are not plotted
+    hidden: Vec<bool>,
+    /// Channels that have stopped receiving data
+    stalled: Vec<bool>,
+    /// Units of the plotted channels, to describe the y axis with
+    units: String,
+    /// Limits of each channel
+    limits: Vec<Limits>,
+    /// Channels shown as their derivative or integral, whose limits are not drawn
+    transformed: Vec<bool>,
     /// Vector of signal channels. Channel numbers are indices
     data_points: Vec<VecDeque<(DateTime<Utc>, Data)>>,
+    /// Events to mark, newest first
+    events: VecDeque<Event>,
     /// Size of the time domain we display
     plot_ms: u64,
     /// Start time of graphing in unix epoch
@@ -160,12 +626,29 @@ struct SignalChart {
 }
 
 impl SignalChart {
-    fn new(num_channels: usize, start_time_ms: i64) -> Self {
-        let data_points = vec![VecDeque::new(); num_channels];
+    fn new(config: &Config, start_time_ms: i64) -> Self {
+        let data_points = vec![VecDeque::new(); config.channels.len()];
+
+        let mut units: Vec<&str> = Vec::new();
+        for channel in config.channels.iter().filter(|c| !c.hidden) {
+            if let Some(unit) = &channel.unit {
+                if !units.contains(&unit.as_str()) {
+                    units.push(unit);
+                }
+            }
+        }
+
         Self {
             cache: Cache::new(),
             colors: get_color_for_channels(data_points.len()),
+            names: config.channel_labels(),
+            hidden: config.channels.iter().map(|c| c.hidden).collect(),
+            stalled: vec![false; config.channels.len()],
+            units: units.join(", "),
+            limits: config.channels.iter().map(|c| c.limits).collect(),
+            transformed: vec![false; config.channels.len()],
             data_points,
+            events: VecDeque::new(),
             latest_reading: chrono::DateTime::default(),
             highest_reading: 1.0,
             lowest_reading: 0.0,
@@ -174,6 +657,53 @@ impl SignalChart {
         }
     }
 
+    /// Removes all readings of a channel and renames it.
+    pub fn reset_channel(&mut self, channel: usize, name: String, transformed: bool) {
+        self.data_points[channel].clear();
+        self.names[channel] = name;
+        self.transformed[channel] = transformed;
+        self.cache.clear();
+    }
+
+    /// Limits drawn for a channel. Limits and alarms apply to raw readings, so none are drawn while the
+    /// channel is shown transformed.
+    fn shown_limits(&self, channel: usize) -> Limits {
+        if self.transformed[channel] {
+            Limits::default()
+        } else {
+            self.limits[channel]
+        }
+    }
+
+    /// Adds an empty channel added while running.
+    fn add_channel(&mut self, name: String) {
+        self.names.push(name);
+        self.hidden.push(false);
+        self.stalled.push(false);
+        self.limits.push(Limits::default());
+        self.transformed.push(false);
+        self.data_points.push(VecDeque::new());
+        self.colors = get_color_for_channels(self.data_points.len());
+    }
+
+    /// Stops plotting a channel that was removed, without clearing the scale of the other channels. Its
+    /// readings are dropped so views computed from the visible window no longer see them.
+    fn remove_channel(&mut self, channel: usize) {
+        self.hidden[channel] = true;
+        self.data_points[channel].clear();
+        self.cache.clear();
+    }
+
+    /// Updates which channels are shown as stalled.
+    fn set_stalled(&mut self, watchdog: &Watchdog) {
+        for (i, stalled) in self.stalled.iter_mut().enumerate() {
+            if *stalled != watchdog.is_stalled(i) {
+                *stalled = !*stalled;
+                self.cache.clear();
+            }
+        }
+    }
+
     /// Updates the scale of the time domain, ie. how much data is on screen at once.
     fn set_time_domain(&mut self, time_ms: u64) {
         self.plot_ms = time_ms;
@@ -183,20 +713,85 @@ impl SignalChart {
         self.plot_ms
     }
 
-    /// Pushes data into its appropriate queue, then trims the old data.
+    /// Oldest time that is visible on the chart.
+    fn oldest_visible(&self) -> DateTime<Utc> {
+        self.latest_reading - chrono::Duration::milliseconds(self.plot_ms as i64)
+    }
+
+    /// Time of the latest reading in ms since the start of graphing. This is the right edge of the chart.
+    pub fn latest_ms(&self) -> i64 {
+        self.latest_reading.timestamp_millis() - self.start_time_ms
+    }
+
+    /// Converts a reading timestamp into seconds since the start of graphing.
+    pub fn seconds_since_start(&self, stamp: DateTime<Utc>) -> f64 {
+        (stamp.timestamp_millis() - self.start_time_ms) as f64 / 1000.0
+    }
+
+    /// Time range of the chart in ms since the start of graphing.
+    pub fn visible_range_ms(&self) -> (i64, i64) {
+        (
+            self.oldest_visible().timestamp_millis() - self.start_time_ms,
+            self.latest_ms(),
+        )
+    }
+
+    /// Gets every reading of a channel the chart retains as `(seconds since start, value)`, oldest
+    /// first. Unlike the other samples, these include text.
+    pub fn retained_samples(&self, channel: usize) -> Vec<(f64, Value)> {
+        self.data_points[channel]
+            .iter()
+            .rev()
+            .map(|(t, d)| (self.seconds_since_start(*t), d.data.clone()))
+            .collect()
+    }
+
+    /// Gets the readings of a channel within the visible time domain as `(seconds since start, data)`,
+    /// oldest first.
+    pub fn visible_samples(&self, channel: usize) -> Vec<(f64, f64)> {
+        self.recent_samples(channel, self.plot_ms)
+    }
+
+    /// Gets the readings of a channel within `span_ms` of the latest reading as
+    /// `(seconds since start, data)`, oldest first. Only as much data as the chart retains is available,
+    /// and text readings are skipped.
+    pub fn recent_samples(&self, channel: usize, span_ms: u64) -> Vec<(f64, f64)> {
+        let oldest_time = self.latest_reading - chrono::Duration::milliseconds(span_ms as i64);
+
+        self.data_points[channel]
+            .iter()
+            .rev()
+            .filter(|(t, _)| *t > oldest_time)
+            .filter_map(|(t, d)| Some((self.seconds_since_start(*t), d.data.number()?)))
+            .collect()
+    }
+
+    /// Pushes data into its appropriate queue in order of time, then trims the old data.
     fn push_data(&mut self, value: Data) {
-        let cur_ms = value.stamp.timestamp_millis();
         const LIMIT: Duration = Duration::from_millis(20_000);
         let limit = Duration::from_millis(self.plot_ms);
+        let (channel, stamp, number) = (value.channel, value.stamp, value.data.number());
+
+        // Stamps of NMEA sentences may go back, such as when a replayed log restarts, so readings are
+        // inserted in order rather than assumed to be the newest
+        let points = &mut self.data_points[channel];
+        let index = points
+            .iter()
+            .position(|(time, _)| *time <= stamp)
+            .unwrap_or(points.len());
+        points.insert(index, (value.stamp, value));
+        let newest_ms = points
+            .front()
+            .map_or(0, |(time, _)| time.timestamp_millis());
 
-        self.data_points[value.channel].push_front((value.stamp, value));
         // Trim data if it is older than the largest timespan visible on the graph
         loop {
-            if let Some((time, _)) = self.data_points[value.channel].back() {
-                let diff = Duration::from_millis((cur_ms - time.timestamp_millis()) as u64);
+            if let Some((time, _)) = self.data_points[channel].back() {
+                let diff =
+                    Duration::from_millis((newest_ms - time.timestamp_millis()).max(0) as u64);
                 // Cut off at 20s by default, but allow for greater spans if one is selected
                 if LIMIT > limit && diff > LIMIT || LIMIT < limit && diff > limit {
-                    self.data_points[value.channel].pop_back();
+                    self.data_points[channel].pop_back();
                     continue;
                 }
             }
@@ -205,22 +800,60 @@ impl SignalChart {
         self.cache.clear();
 
         // If this reading is newer than any other, mark that as our latest
-        if value.stamp > self.latest_reading {
-            self.latest_reading = value.stamp;
+        if stamp > self.latest_reading {
+            self.latest_reading = stamp;
         }
 
+        // Hidden channels and text do not affect the scale of the plot
+        let number = match number {
+            Some(number) if !self.hidden[channel] => number,
+            _ => return,
+        };
+
         // First reading 'zeros' the graph, later readings rescale the y axis
-        if self.data_points[value.channel].len() == 1 {
-            self.lowest_reading = value.data;
-            self.highest_reading = value.data;
-        } else if value.data > self.highest_reading {
-            self.highest_reading = value.data;
-        } else if value.data < self.lowest_reading {
-            self.lowest_reading = value.data;
+        if self.data_points[channel].len() == 1 {
+            self.lowest_reading = number;
+            self.highest_reading = number;
+        } else if number > self.highest_reading {
+            self.highest_reading = number;
+        } else if number < self.lowest_reading {
+            self.lowest_reading = number;
+        }
+    }
+
+    /// Adds an event to mark, trimming events older than any data kept.
+    fn push_event(&mut self, event: Event) {
+        let span = chrono::Duration::milliseconds(self.plot_ms.max(20_000) as i64);
+        let oldest = event.stamp - span;
+
+        if event.stamp > self.latest_reading {
+            self.latest_reading = event.stamp;
+        }
+        self.events.push_front(event);
+        while self.events.back().is_some_and(|e| e.stamp < oldest) {
+            self.events.pop_back();
+        }
+        self.cache.clear();
+    }
+
+    /// Range of the y axis, which fits the readings and the limits of plotted channels.
+    fn y_range(&self) -> std::ops::Range<f64> {
+        let (mut low, mut high) = (self.lowest_reading, self.highest_reading);
+
+        for limits in (0..self.limits.len())
+            .filter(|i| !self.hidden[*i])
+            .map(|i| self.shown_limits(i))
+        {
+            for limit in [limits.lower, limits.upper].into_iter().flatten() {
+                low = low.min(limit);
+                high = high.max(limit);
+            }
         }
+
+        low..high
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         Container::new(
             Column::new()
                 .width(Length::Fill)
@@ -250,17 +883,17 @@ impl Chart<Message> for SignalChart {
         use plotters::{prelude::*, style::Color};
 
         // Dynamically size the y axis as data comes in, then plot all data in the selected time domain
-        let oldest_time = self.latest_reading - chrono::Duration::milliseconds(self.plot_ms as i64);
+        let oldest_time = self.oldest_visible();
 
-        // Configure context
+        // Configure context, leaving room to describe the y axis if there are units
         let mut chart = chart
             .x_label_area_size(28)
-            .y_label_area_size(28)
+            .y_label_area_size(if self.units.is_empty() { 28 } else { 48 })
             .margin(20)
             .build_cartesian_2d(
                 oldest_time.timestamp_millis() - self.start_time_ms
                     ..self.latest_reading.timestamp_millis() - self.start_time_ms,
-                self.lowest_reading..self.highest_reading,
+                self.y_range(),
             )
             .expect("failed to build chart");
 
@@ -277,6 +910,8 @@ impl Chart<Message> for SignalChart {
                     .color(&BLUE.mix(0.80))
                     .transform(FontTransform::Rotate90),
             )
+            .y_desc(&self.units)
+            .axis_desc_style(("sans-serif", 15).into_font().color(&BLUE.mix(0.80)))
             .x_label_formatter(&|d| format!("{}ms", d))
             .x_labels(10)
             .x_label_style(
@@ -290,23 +925,97 @@ impl Chart<Message> for SignalChart {
 
         // Plot each channel
         for (i, channel) in self.data_points.iter().enumerate() {
-            if !channel.is_empty() {
+            if !channel.is_empty() && !self.hidden[i] {
                 let color = self.colors[i];
 
-                chart
+                let series = chart
                     .draw_series(LineSeries::new(
                         channel
                             .iter()
                             .filter(|(t, _)| *t > oldest_time) //Only plot in bounds
-                            .map(|x| (x.0.timestamp_millis() - self.start_time_ms, x.1.data)),
+                            .filter_map(|(t, d)| {
+                                Some((t.timestamp_millis() - self.start_time_ms, d.data.number()?))
+                            }),
                         color,
                     ))
-                    .expect("failed to draw chart data")
-                    .label(format!("channel {}", i))
-                    .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+                    .expect("failed to draw chart data");
+
+                // Grey out stalled channels in the legend
+                if self.stalled[i] {
+                    series
+                        .label(format!("{} (stalled)", self.names[i]))
+                        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], STALLED));
+                } else {
+                    series
+                        .label(&self.names[i])
+                        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+                }
+
+                let limits = self.shown_limits(i);
+                if limits.is_set() {
+                    let (left, right) = (
+                        oldest_time.timestamp_millis() - self.start_time_ms,
+                        self.latest_ms(),
+                    );
+
+                    // Draw limits as lines across the chart
+                    chart
+                        .draw_series([limits.lower, limits.upper].into_iter().flatten().map(
+                            |limit| {
+                                PathElement::new(
+                                    vec![(left, limit), (right, limit)],
+                                    color.mix(0.6).stroke_width(2),
+                                )
+                            },
+                        ))
+                        .expect("failed to draw limits");
+
+                    // Highlight readings outside the limits
+                    chart
+                        .draw_series(
+                            channel
+                                .iter()
+                                .filter(|(t, _)| *t > oldest_time)
+                                .filter_map(|(t, d)| Some((t, d.data.number()?)))
+                                .filter(|(_, data)| limits.violated_by(*data).is_some())
+                                .map(|(t, data)| {
+                                    Circle::new(
+                                        (t.timestamp_millis() - self.start_time_ms, data),
+                                        3,
+                                        RED.filled(),
+                                    )
+                                }),
+                        )
+                        .expect("failed to draw limit violations");
+                }
             }
         }
 
+        // Mark events with labelled vertical lines
+        let y_range = self.y_range();
+        let events: Vec<(i64, &Event)> = self
+            .events
+            .iter()
+            .filter(|e| e.stamp > oldest_time)
+            .map(|e| (e.stamp.timestamp_millis() - self.start_time_ms, e))
+            .collect();
+
+        chart
+            .draw_series(events.iter().map(|(t, _)| {
+                PathElement::new(vec![(*t, y_range.start), (*t, y_range.end)], BLACK.mix(0.5))
+            }))
+            .expect("failed to draw events");
+        chart
+            .draw_series(events.iter().map(|(t, e)| {
+                EmptyElement::at((*t, y_range.end))
+                    + Text::new(
+                        e.label.clone(),
+                        (4, 4),
+                        ("sans-serif", 14).into_font().color(&BLACK.mix(0.7)),
+                    )
+            }))
+            .expect("failed to draw event labels");
+
         // Draw legend
         chart
             .configure_series_labels()
@@ -322,3 +1031,33 @@ impl Chart<Message> for SignalChart {
         self.cache.draw(bounds, draw_fn)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_going_back() {
+        let start = Utc::now();
+        let mut chart = SignalChart::new(&Config::default(), start.timestamp_millis());
+        let push = |chart: &mut SignalChart, offset_ms: i64| {
+            chart.push_data(Data {
+                stamp: start + chrono::Duration::milliseconds(offset_ms),
+                channel: 0,
+                data: Value::Number(offset_ms as f64),
+            })
+        };
+
+        push(&mut chart, 0);
+        push(&mut chart, 1000);
+        // A late reading is kept in order, and one from long before does not clear the history
+        push(&mut chart, 500);
+        push(&mut chart, -3_600_000);
+
+        let stamps: Vec<f64> = chart.data_points[0]
+            .iter()
+            .filter_map(|(_, d)| d.data.number())
+            .collect();
+        assert_eq!(stamps, [1000.0, 500.0, 0.0]);
+    }
+}
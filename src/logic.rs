@@ -0,0 +1,259 @@
+//! Logic and state channels, which are shown as lanes of a timing diagram under the signal chart
+
+use crate::color_table::get_color_for_channels;
+use crate::extractor::{Config, Source, Value};
+use crate::interface::{Message, SignalChart, FONT_BOLD, FONT_REGULAR};
+use iced::widget::canvas::{Cache, Frame, Geometry};
+use iced::{Element, Length, Size};
+use plotters::prelude::ChartBuilder;
+use plotters_iced::plotters_backend::DrawingBackend;
+use plotters_iced::{Chart, ChartWidget};
+use std::str::FromStr;
+
+/// Height of each lane in pixels
+const LANE_HEIGHT: u16 = 40;
+
+/// Number of distinct colors states are drawn with
+const STATE_COLORS: usize = 16;
+
+/// Names of the states of a logic channel, as given on the command line.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateNames(pub Vec<String>);
+
+impl FromStr for StateNames {
+    type Err = String;
+
+    /// Parses names separated by commas, such as `IDLE,RUN,FAULT`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let names: Vec<String> = s.split(',').map(|n| n.trim().to_string()).collect();
+
+        if names.iter().any(String::is_empty) {
+            return Err(format!("`{s}` has an empty state name"));
+        }
+
+        Ok(Self(names))
+    }
+}
+
+/// Parses a captured logic level or state into its value.
+///
+/// With state names, the value is the index of the name. Otherwise levels such as `1`, `true`, `high` or
+/// `on` are 1, their opposites are 0, and other integers are taken as they are.
+pub fn parse_state(text: &str, states: &StateNames) -> Option<f64> {
+    if !states.0.is_empty() {
+        return states.0.iter().position(|s| s == text).map(|i| i as f64);
+    }
+
+    match text.to_ascii_lowercase().as_str() {
+        "1" | "true" | "high" | "on" => Some(1.0),
+        "0" | "false" | "low" | "off" => Some(0.0),
+        other => other.parse::<i64>().ok().map(|v| v as f64),
+    }
+}
+
+/// A lane of the timing diagram
+struct Lane {
+    channel: usize,
+    name: String,
+    states: StateNames,
+    /// Changes of state as (ms since start, value), oldest first
+    changes: Vec<(i64, Value)>,
+}
+
+impl Lane {
+    /// Text shown in a band of this lane
+    fn label(&self, value: &Value) -> String {
+        match value {
+            Value::Number(n) => self
+                .states
+                .0
+                .get(*n as usize)
+                .cloned()
+                .unwrap_or_else(|| n.to_string()),
+            other => other.to_string(),
+        }
+    }
+
+    /// Whether a value is drawn as a high or low level instead of a labelled band
+    fn is_level(&self, value: &Value) -> bool {
+        self.states.0.is_empty() && matches!(value, Value::Number(n) if *n == 0.0 || *n == 1.0)
+    }
+}
+
+/// Picks the color of a state, so each state is always drawn the same way.
+fn state_color(value: &Value) -> usize {
+    match value {
+        Value::Number(n) => (*n as i64).unsigned_abs() as usize % STATE_COLORS,
+        // Sum the bytes rather than hash them, so colors are the same between runs
+        other => other.to_string().bytes().map(usize::from).sum::<usize>() % STATE_COLORS,
+    }
+}
+
+/// Widget that displays logic channels as a timing diagram
+pub struct LogicChart {
+    cache: Cache,
+    lanes: Vec<Lane>,
+    /// Visible time range in ms since start, matching the signal chart
+    range: (i64, i64),
+}
+
+impl LogicChart {
+    /// Creates the timing diagram, or None if there are no logic or state channels.
+    pub fn new(config: &Config) -> Option<Self> {
+        let lanes: Vec<Lane> = config
+            .channels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match &c.source {
+                Source::Logic(_, states) => Some(Lane {
+                    channel: i,
+                    name: c.label(),
+                    states: states.clone(),
+                    changes: Vec::new(),
+                }),
+                Source::State(_) => Some(Lane {
+                    channel: i,
+                    name: c.label(),
+                    states: StateNames::default(),
+                    changes: Vec::new(),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        (!lanes.is_empty()).then(|| Self {
+            cache: Cache::new(),
+            lanes,
+            range: (0, 1),
+        })
+    }
+
+    /// Copies the readings of each lane from the signal chart. Readings from before the visible range
+    /// are kept too, as they give the state at its start.
+    pub fn update(&mut self, signal: &SignalChart) {
+        self.range = signal.visible_range_ms();
+
+        for lane in &mut self.lanes {
+            lane.changes.clear();
+
+            for (seconds, value) in signal.retained_samples(lane.channel) {
+                if lane.changes.last().map(|(_, v)| v) != Some(&value) {
+                    lane.changes.push(((seconds * 1000.0) as i64, value));
+                }
+            }
+        }
+
+        self.cache.clear();
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        ChartWidget::new(self)
+            .height(Length::Units(LANE_HEIGHT * self.lanes.len() as u16 + 48))
+            .resolve_font(|_, style| match style {
+                plotters_iced::plotters_backend::FontStyle::Bold => FONT_BOLD,
+                _ => FONT_REGULAR,
+            })
+            .into()
+    }
+}
+
+impl Chart<Message> for LogicChart {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut chart: ChartBuilder<DB>) {
+        use plotters::{prelude::*, style::Color};
+
+        let (oldest, latest) = self.range;
+        let lanes = self.lanes.len() as f64;
+
+        let mut chart = chart
+            .x_label_area_size(28)
+            .y_label_area_size(28)
+            .margin(10)
+            .build_cartesian_2d(oldest..latest, 0.0..lanes)
+            .expect("failed to build chart");
+
+        chart
+            .configure_mesh()
+            .disable_y_mesh()
+            .disable_y_axis()
+            .bold_line_style(BLUE.mix(0.4))
+            .light_line_style(BLUE.mix(0.2))
+            .axis_style(ShapeStyle::from(BLUE.mix(0.80)).stroke_width(1))
+            .x_label_formatter(&|d| format!("{}ms", d))
+            .x_labels(10)
+            .x_label_style(
+                ("sans-serif", 15)
+                    .into_font()
+                    .color(&BLUE.mix(0.80))
+                    .transform(FontTransform::Rotate90),
+            )
+            .draw()
+            .expect("failed to draw chart mesh");
+
+        let font = ("sans-serif", 14).into_font();
+
+        // Lanes are stacked from the top in channel order
+        for (i, lane) in self.lanes.iter().enumerate() {
+            let base = lanes - (i + 1) as f64;
+            let (low, high) = (base + 0.15, base + 0.75);
+
+            for (j, (start, value)) in lane.changes.iter().enumerate() {
+                let start = (*start).max(oldest);
+                let end = lane.changes.get(j + 1).map_or(latest, |(t, _)| *t);
+                if end <= oldest {
+                    continue;
+                }
+
+                if lane.is_level(value) {
+                    let y = if value.number() == Some(1.0) {
+                        high
+                    } else {
+                        low
+                    };
+                    // Draw an edge where the level changed, unless that is off the chart
+                    let mut points = if j > 0 && start > oldest {
+                        vec![(start, low), (start, high)]
+                    } else {
+                        Vec::new()
+                    };
+                    points.extend([(start, y), (end, y)]);
+
+                    chart
+                        .draw_series(std::iter::once(PathElement::new(
+                            points,
+                            BLACK.stroke_width(2),
+                        )))
+                        .expect("failed to draw logic level");
+                } else {
+                    let color = get_color_for_channels(STATE_COLORS)[state_color(value)];
+
+                    chart
+                        .draw_series(std::iter::once(Rectangle::new(
+                            [(start, low), (end, high)],
+                            color.mix(0.5).filled(),
+                        )))
+                        .expect("failed to draw logic state");
+                    chart
+                        .draw_series(std::iter::once(
+                            EmptyElement::at((start, high))
+                                + Text::new(lane.label(value), (4, 4), font.clone()),
+                        ))
+                        .expect("failed to draw logic state label");
+                }
+            }
+
+            chart
+                .draw_series(std::iter::once(
+                    EmptyElement::at((oldest, base + 1.0))
+                        + Text::new(lane.name.clone(), (4, 0), font.clone().color(&BLUE)),
+                ))
+                .expect("failed to draw lane name");
+        }
+    }
+
+    #[inline]
+    fn draw<F: Fn(&mut Frame)>(&self, bounds: Size, draw_fn: F) -> Geometry {
+        self.cache.draw(bounds, draw_fn)
+    }
+}
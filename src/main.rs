@@ -1,16 +1,55 @@
+mod alarm;
+mod array;
+mod binary;
+mod calibration;
+mod cbor;
 mod cli;
 mod color_table;
+mod colormap;
+mod console;
+mod counter;
+mod crc;
+mod derive;
+mod dsp;
 mod extractor;
+mod filter;
+mod frame;
+mod framing;
+mod histogram;
 mod interface;
+mod logic;
+mod msgpack;
+mod nmea;
+mod number;
+mod record;
+mod regex_editor;
+mod spectrogram;
+mod spectrum;
+mod stats;
+mod status;
+mod transform;
+mod watchdog;
+mod waterfall;
 
-use crate::extractor::Config;
+use crate::alarm::{AlarmActions, Limits};
+use crate::binary::Layout;
+use crate::calibration::Calibration;
+use crate::derive::Expr;
+use crate::extractor::{Channel, Config, Source};
+use crate::filter::FilterDisplay;
+use crate::histogram::HistogramSettings;
 use crate::interface::*;
+use crate::logic::StateNames;
+use crate::spectrogram::SpectrogramSettings;
+use crate::spectrum::SpectrumSettings;
+use crate::waterfall::WaterfallSettings;
 use clap::Parser;
 use iced::window::Icon;
 use iced::{Application, Settings};
 use regex::Regex;
 use simplelog::ConfigBuilder;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 fn main() {
     let args = cli::Args::parse();
@@ -24,25 +63,74 @@ fn main() {
     .unwrap();
 
     log::debug!("Regex Vec: {:?}", args.regexes);
+    log::debug!("Expressions: {:?}", args.exprs);
     log::debug!("csv path: {:?}", args.csv);
 
+    let config = build_config(&args);
+
+    if let Some(t) = args
+        .transforms
+        .iter()
+        .find(|t| t.channel >= config.channels.len())
+    {
+        panic!("Invalid transform: channel {} does not exist", t.channel);
+    }
+
+    if let Some(channel) = args.fft.filter(|c| *c >= config.channels.len()) {
+        panic!("Invalid FFT: channel {channel} does not exist");
+    }
+    if let Some(channel) = args.spectrogram.filter(|c| *c >= config.channels.len()) {
+        panic!("Invalid spectrogram: channel {channel} does not exist");
+    }
+    if let Some(channel) = args.histogram.filter(|c| *c >= config.channels.len()) {
+        panic!("Invalid histogram: channel {channel} does not exist");
+    }
+
+    if let Some(channel) = args.waterfall {
+        if !matches!(
+            config.channels.get(channel),
+            Some(Channel {
+                source: Source::Array(_),
+                ..
+            })
+        ) {
+            panic!("Invalid waterfall: channel {channel} is not an array channel");
+        }
+    }
+
     log::info!("Creating gui...");
 
     State::run(Settings {
         antialiasing: true,
         default_font: Some(include_bytes!("../fonts/notosans-regular.ttf")),
         flags: Flags {
-            extractor_conf: Arc::new(Config {
-                matchers: args.regexes.map_or_else(
-                    || Config::default().matchers,
-                    |r| {
-                        r.iter()
-                            .map(|s| Regex::new(s).expect("Invalid Regex!"))
-                            .collect()
-                    },
-                ),
-                csv: args.csv,
+            extractor_conf: Arc::new(RwLock::new(config)),
+            spectrum: args.fft.map(|channel| SpectrumSettings {
+                channel,
+                window: args.fft_window,
+                magnitude: args.fft_scale,
+            }),
+            spectrogram: args.spectrogram.map(|channel| SpectrogramSettings {
+                channel,
+                colormap: args.colormap,
+                window: args.fft_window,
+                history_ms: args.spectrogram_history,
+                hop_ms: args.spectrogram_hop,
+                span_ms: args.spectrogram_span,
             }),
+            waterfall: args.waterfall.map(|channel| WaterfallSettings {
+                channel,
+                colormap: args.colormap,
+                history_ms: args.waterfall_history,
+            }),
+            histogram: args.histogram.map(|channel| HistogramSettings {
+                channel,
+                bins: args.histogram_bins as usize,
+            }),
+            stats: args.stats,
+            transforms: args.transforms,
+            stall_timeout: args.stall_timeout.map(Duration::from_millis),
+            console_lines: args.console_lines as usize,
         },
         window: iced::window::Settings {
             icon: Some(
@@ -55,3 +143,311 @@ fn main() {
     })
     .unwrap();
 }
+
+/// Creates the extractor config from the command line arguments.
+fn build_config(args: &cli::Args) -> Config {
+    let layout = args.layout.as_ref().map(|path| {
+        let text = std::fs::read_to_string(path).expect("Failed to read layout file!");
+        text.parse::<Layout>()
+            .unwrap_or_else(|e| panic!("Invalid layout {}: {e}", path.display()))
+    });
+
+    let nmea = !args.nmea.is_empty();
+    if [layout.is_some(), args.records.is_some(), nmea]
+        .iter()
+        .filter(|i| **i)
+        .count()
+        > 1
+    {
+        panic!("Invalid input: only one of a layout, records and NMEA sentences can be decoded");
+    }
+    if args.records.is_some() && args.keys.is_empty() {
+        panic!("Invalid records: no key paths given to read channels from");
+    }
+    if args.records.is_none() && !args.keys.is_empty() {
+        panic!("Invalid key paths: they need --records to be set");
+    }
+    if args.nmea_time && !nmea {
+        panic!("Invalid NMEA time: no NMEA fields given to read channels from");
+    }
+
+    // Binary input has no text to match regexes against, and NMEA sentences are not matched with regexes
+    if (layout.is_some() || args.records.is_some() || nmea)
+        && (args.regexes.is_some()
+            || !args.counts.is_empty()
+            || !args.logic.is_empty()
+            || !args.states.is_empty()
+            || !args.arrays.is_empty()
+            || !args.events.is_empty())
+    {
+        panic!(
+            "Invalid input: regex, count, logic, state, array and event channels need text matched with regexes"
+        );
+    }
+
+    let mut config = Config {
+        channels: match (&layout, &args.regexes) {
+            // Each key path of a record is a channel
+            _ if args.records.is_some() => args
+                .keys
+                .iter()
+                .map(|path| Channel::new(path.to_string(), Source::Key(path.clone())))
+                .collect(),
+            // Each field of NMEA sentences is a channel
+            _ if nmea => args
+                .nmea
+                .iter()
+                .map(|field| Channel::new(field.to_string(), Source::Nmea(field.clone())))
+                .collect(),
+            // Each field of a binary layout is a channel
+            (Some(layout), _) => layout
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, f)| Channel::new(f.name.clone(), Source::Field(i)))
+                .collect(),
+            (None, Some(r)) => r
+                .iter()
+                .enumerate()
+                .map(|(i, s)| Channel::regex(i, Regex::new(s).expect("Invalid Regex!")))
+                .collect(),
+            (None, None) => Config::default().channels,
+        },
+        layout,
+        records: args.records,
+        nmea,
+        nmea_time: args.nmea_time,
+        alignment: args.align,
+        count_bucket_ms: args.count_bucket,
+        events: args
+            .events
+            .iter()
+            .map(|s| Regex::new(s).expect("Invalid event Regex!"))
+            .collect(),
+        csv: args.csv.clone(),
+        alarm: AlarmActions {
+            command: args.alarm_command.clone(),
+            stderr: args.alarm_stderr,
+            exit_code: args.alarm_exit,
+        },
+        integral_resets: 0,
+    };
+
+    for s in &args.scales {
+        calibration_of(&mut config, s.channel).scale = s.value;
+    }
+    for s in &args.offsets {
+        calibration_of(&mut config, s.channel).offset = s.value;
+    }
+    for s in &args.luts {
+        calibration_of(&mut config, s.channel).table = Some(s.value.clone());
+    }
+    for s in &args.number_formats {
+        match config.channels.get_mut(s.channel) {
+            Some(Channel {
+                source: Source::Regex(_) | Source::Key(_),
+                format,
+                ..
+            }) => *format = s.value.clone(),
+            _ => panic!(
+                "Invalid number format: channel {} is not a regex or key channel",
+                s.channel
+            ),
+        }
+    }
+    for s in &args.integers {
+        match config.channels.get_mut(s.channel) {
+            Some(Channel {
+                source: Source::Regex(_) | Source::Key(_),
+                format,
+                ..
+            }) => format.integer = Some(s.value),
+            _ => panic!(
+                "Invalid integer format: channel {} is not a regex or key channel",
+                s.channel
+            ),
+        }
+    }
+
+    // Counter channels are appended after the regex channels
+    for s in &args.counts {
+        let mut channel = Channel::new(
+            format!("{s} rate"),
+            Source::Counter(Regex::new(s).expect("Invalid count Regex!")),
+        );
+        channel.unit = Some("/s".to_string());
+        config.channels.push(channel);
+    }
+
+    // Logic channels are appended after the counter channels, and drawn in their own lanes
+    for s in &args.logic {
+        let mut channel = Channel::new(
+            format!("logic {}", config.channels.len()),
+            Source::Logic(
+                Regex::new(s).expect("Invalid logic Regex!"),
+                StateNames::default(),
+            ),
+        );
+        channel.hidden = true;
+        config.channels.push(channel);
+    }
+    for s in &args.logic_states {
+        match config.channels.get_mut(s.channel) {
+            Some(Channel {
+                source: Source::Logic(_, states),
+                ..
+            }) => *states = s.value.clone(),
+            _ => panic!(
+                "Invalid logic states: channel {} is not a logic channel",
+                s.channel
+            ),
+        }
+    }
+
+    // State channels are appended after the logic channels, and share their lanes
+    for s in &args.states {
+        let mut channel = Channel::new(
+            format!("state {}", config.channels.len()),
+            Source::State(Regex::new(s).expect("Invalid state Regex!")),
+        );
+        channel.hidden = true;
+        config.channels.push(channel);
+    }
+
+    // Array channels are appended after the state channels, and drawn as their latest frame
+    for s in &args.arrays {
+        let mut channel = Channel::new(
+            format!("array {}", config.channels.len()),
+            Source::Array(Regex::new(s).expect("Invalid array Regex!")),
+        );
+        channel.hidden = true;
+        config.channels.push(channel);
+    }
+    // Fanned out arrays are split into a channel per element instead
+    for s in &args.fan_outs {
+        let name = match config.channels.get(s.channel) {
+            Some(Channel {
+                source: Source::Array(_),
+                name,
+                ..
+            }) => name.clone(),
+            _ => panic!(
+                "Invalid fan out: channel {} is not an array channel",
+                s.channel
+            ),
+        };
+        for index in 0..s.value {
+            config.channels.push(Channel::new(
+                format!("{name}[{index}]"),
+                Source::Element(s.channel, index),
+            ));
+        }
+    }
+
+    // Derived channels are appended after the source channels, and can only see channels before them
+    for def in &args.exprs {
+        let (name, src) = derive::split_definition(def)
+            .and_then(|(name, src)| {
+                if config.channel_index(name).is_some() {
+                    Err(format!("channel `{name}` already exists"))
+                } else {
+                    Ok((name, src))
+                }
+            })
+            .unwrap_or_else(|e| panic!("Invalid expression: {e}"));
+        let expr = Expr::parse(src, |n| config.channel_index(n))
+            .unwrap_or_else(|e| panic!("Invalid expression `{def}`: {e}"));
+
+        let mut inputs = Vec::new();
+        expr.inputs(&mut inputs);
+        if inputs.is_empty() {
+            panic!("Invalid expression `{def}`: it must refer to at least one channel");
+        }
+
+        config
+            .channels
+            .push(Channel::new(name, Source::Derived(expr)));
+    }
+
+    for spec in &args.filters {
+        let input = config
+            .channels
+            .get_mut(spec.channel)
+            .unwrap_or_else(|| panic!("Invalid filter: channel {} does not exist", spec.channel));
+
+        if args.filter_display == FilterDisplay::Replace {
+            input.hidden = true;
+        }
+
+        let mut channel = Channel::new(
+            format!("{} ({})", input.name, spec.kind),
+            Source::Filter(spec.channel, spec.kind),
+        );
+        channel.logged = args.filter_csv;
+        config.channels.push(channel);
+    }
+
+    // The gui shows transforms itself, so these channels are hidden and only written to the CSV
+    if args.transform_csv {
+        for spec in &args.transforms {
+            let input = config.channels.get(spec.channel).unwrap_or_else(|| {
+                panic!("Invalid transform: channel {} does not exist", spec.channel)
+            });
+
+            let mut channel = Channel::new(
+                format!("{}{}", input.name, spec.transform.suffix()),
+                Source::Transform(spec.channel, spec.transform),
+            );
+            channel.hidden = true;
+            config.channels.push(channel);
+        }
+    }
+
+    // Units can be set on any channel, so are applied last
+    for unit in &args.units {
+        config
+            .channels
+            .get_mut(unit.channel)
+            .unwrap_or_else(|| panic!("Invalid unit: channel {} does not exist", unit.channel))
+            .unit = Some(unit.value.clone());
+    }
+
+    for limit in &args.lower {
+        limits_of(&mut config, limit.channel).lower = Some(limit.value);
+    }
+    for limit in &args.upper {
+        limits_of(&mut config, limit.channel).upper = Some(limit.value);
+    }
+
+    // Filtering does not change units, so filtered channels default to the unit of their input
+    for i in 0..config.channels.len() {
+        if let Source::Filter(input, _) = config.channels[i].source {
+            if config.channels[i].unit.is_none() {
+                config.channels[i].unit = config.channels[input].unit.clone();
+            }
+        }
+    }
+
+    config
+}
+
+/// Gets the calibration of a channel read from stdin to modify, creating it if needed.
+fn calibration_of(config: &mut Config, channel: usize) -> &mut Calibration {
+    match config.channels.get_mut(channel) {
+        Some(Channel {
+            source: Source::Regex(_) | Source::Field(_) | Source::Key(_) | Source::Nmea(_),
+            calibration,
+            ..
+        }) => calibration.get_or_insert_with(Calibration::default),
+        _ => panic!("Invalid calibration: channel {channel} is not read from stdin"),
+    }
+}
+
+/// Gets the limits of a channel to modify.
+fn limits_of(config: &mut Config, channel: usize) -> &mut Limits {
+    &mut config
+        .channels
+        .get_mut(channel)
+        .unwrap_or_else(|| panic!("Invalid limit: channel {channel} does not exist"))
+        .limits
+}
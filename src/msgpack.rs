@@ -0,0 +1,102 @@
+//! Decoding of MessagePack items
+
+use crate::record::{DecodeError, Item, Reader, MAX_DEPTH};
+
+/// Decodes one item, with `depth` arrays or maps around it.
+pub fn decode(reader: &mut Reader, depth: usize) -> Result<Item, DecodeError> {
+    if depth > MAX_DEPTH {
+        return Err(DecodeError::Invalid);
+    }
+
+    let marker = reader.byte()?;
+    Ok(match marker {
+        0x00..=0x7F => Item::Int(marker as i128),
+        0x80..=0x8F => map(reader, (marker & 0x0F) as usize, depth)?,
+        0x90..=0x9F => array(reader, (marker & 0x0F) as usize, depth)?,
+        0xA0..=0xBF => Item::Str(reader.str((marker & 0x1F) as usize)?),
+        0xC0 => Item::Null,
+        0xC2 => Item::Bool(false),
+        0xC3 => Item::Bool(true),
+        0xC4..=0xC6 => {
+            let len = reader.uint(1 << (marker - 0xC4))? as usize;
+            Item::Bytes(reader.take(len)?.to_vec())
+        }
+        // Extensions keep their data as bytes, dropping their type
+        0xC7..=0xC9 => {
+            let len = reader.uint(1 << (marker - 0xC7))? as usize;
+            reader.byte()?;
+            Item::Bytes(reader.take(len)?.to_vec())
+        }
+        0xCA => Item::Float(f32::from_bits(reader.uint(4)? as u32) as f64),
+        0xCB => Item::Float(f64::from_bits(reader.uint(8)?)),
+        0xCC..=0xCF => Item::Int(reader.uint(1 << (marker - 0xCC))? as i128),
+        0xD0..=0xD3 => {
+            let size = 1 << (marker - 0xD0);
+            let value = reader.uint(size)?;
+            // Sign extend from the size of the integer
+            let shift = 64 - 8 * size as u32;
+            Item::Int(((value << shift) as i64 >> shift) as i128)
+        }
+        0xD4..=0xD8 => {
+            reader.byte()?;
+            Item::Bytes(reader.take(1 << (marker - 0xD4))?.to_vec())
+        }
+        0xD9..=0xDB => {
+            let len = reader.uint(1 << (marker - 0xD9))? as usize;
+            Item::Str(reader.str(len)?)
+        }
+        0xDC | 0xDD => {
+            let len = reader.uint(2 << (marker - 0xDC))? as usize;
+            array(reader, len, depth)?
+        }
+        0xDE | 0xDF => {
+            let len = reader.uint(2 << (marker - 0xDE))? as usize;
+            map(reader, len, depth)?
+        }
+        0xE0..=0xFF => Item::Int(marker as i8 as i128),
+        // 0xC1 is never used
+        _ => return Err(DecodeError::Invalid),
+    })
+}
+
+fn array(reader: &mut Reader, len: usize, depth: usize) -> Result<Item, DecodeError> {
+    (0..len)
+        .map(|_| decode(reader, depth + 1))
+        .collect::<Result<_, _>>()
+        .map(Item::Array)
+}
+
+fn map(reader: &mut Reader, len: usize, depth: usize) -> Result<Item, DecodeError> {
+    (0..len)
+        .map(|_| Ok((decode(reader, depth + 1)?, decode(reader, depth + 1)?)))
+        .collect::<Result<_, _>>()
+        .map(Item::Map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_bytes(bytes: &[u8]) -> Item {
+        decode(&mut Reader::new(bytes), 0).unwrap()
+    }
+
+    #[test]
+    fn sign_extension() {
+        assert_eq!(decode_bytes(&[0xE0]), Item::Int(-32));
+        assert_eq!(decode_bytes(&[0xD0, 0xFF]), Item::Int(-1));
+        assert_eq!(decode_bytes(&[0xD0, 0x80]), Item::Int(-128));
+        assert_eq!(decode_bytes(&[0xD1, 0x80, 0x00]), Item::Int(-32768));
+        assert_eq!(decode_bytes(&[0xD2, 0xFF, 0xFF, 0xFF, 0xFE]), Item::Int(-2));
+        assert_eq!(
+            decode_bytes(&[0xD3, 0x80, 0, 0, 0, 0, 0, 0, 0]),
+            Item::Int(i64::MIN as i128)
+        );
+        // Unsigned integers are not sign extended
+        assert_eq!(decode_bytes(&[0xCC, 0xFF]), Item::Int(255));
+        assert_eq!(
+            decode_bytes(&[0xCF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]),
+            Item::Int(u64::MAX as i128)
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! Parsing of NMEA 0183 sentences from GPS receivers and marine instruments
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// How the text of a field becomes a reading
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Conversion {
+    Number,
+    /// `ddmm.mmmm` degrees and minutes, negated when the hemisphere field at this index is `S` or `W`
+    Coordinate(usize),
+    /// A number negated when the direction field at this index is `W`
+    Direction(usize),
+    /// `A` (valid) as 1, and `V` (void) as 0
+    Status,
+}
+
+/// Named fields of common sentences, with their index and conversion
+const NAMED_FIELDS: [(&str, &str, usize, Conversion); 17] = [
+    ("GGA", "lat", 2, Conversion::Coordinate(3)),
+    ("GGA", "lon", 4, Conversion::Coordinate(5)),
+    ("GGA", "quality", 6, Conversion::Number),
+    ("GGA", "satellites", 7, Conversion::Number),
+    ("GGA", "hdop", 8, Conversion::Number),
+    ("GGA", "altitude", 9, Conversion::Number),
+    ("GGA", "separation", 11, Conversion::Number),
+    ("RMC", "status", 2, Conversion::Status),
+    ("RMC", "lat", 3, Conversion::Coordinate(4)),
+    ("RMC", "lon", 5, Conversion::Coordinate(6)),
+    ("RMC", "speed", 7, Conversion::Number),
+    ("RMC", "course", 8, Conversion::Number),
+    ("RMC", "variation", 10, Conversion::Direction(11)),
+    ("VTG", "course", 1, Conversion::Number),
+    ("VTG", "magnetic", 3, Conversion::Number),
+    ("VTG", "knots", 5, Conversion::Number),
+    ("VTG", "kmh", 7, Conversion::Number),
+];
+
+/// A sentence with a valid checksum, such as `$GPGGA,...*47`
+pub struct Sentence<'a> {
+    /// Talker and sentence type, such as `GPGGA`
+    address: &'a str,
+    /// Fields after the address
+    fields: Vec<&'a str>,
+}
+
+impl<'a> Sentence<'a> {
+    /// Parses a line, or None if it is not a sentence with a valid checksum.
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let body = line.trim().strip_prefix(['$', '!'])?;
+        let (body, checksum) = body.split_once('*')?;
+        let checksum = u8::from_str_radix(checksum, 16).ok()?;
+        if body.bytes().fold(0, |sum, b| sum ^ b) != checksum {
+            return None;
+        }
+
+        let mut fields = body.split(',');
+        let address = fields.next()?;
+        Some(Self {
+            address,
+            fields: fields.collect(),
+        })
+    }
+
+    /// Whether this is a sentence of a type, such as `GGA` from any talker, or `GPGGA`
+    fn is(&self, sentence: &str) -> bool {
+        self.address == sentence || self.address.get(2..) == Some(sentence)
+    }
+
+    /// Field at an index counted from 1 after the address, as in the NMEA standard, unless it is empty
+    fn field(&self, index: usize) -> Option<&'a str> {
+        self.fields
+            .get(index.checked_sub(1)?)
+            .copied()
+            .filter(|f| !f.is_empty())
+    }
+
+    /// UTC time of day of a GGA, RMC or ZDA sentence
+    fn time(&self) -> Option<NaiveTime> {
+        if !(self.is("GGA") || self.is("RMC") || self.is("ZDA")) {
+            return None;
+        }
+
+        NaiveTime::parse_from_str(self.field(1)?, "%H%M%S%.f").ok()
+    }
+
+    /// Date of an RMC or ZDA sentence
+    fn date(&self) -> Option<NaiveDate> {
+        if self.is("RMC") {
+            let date = self.field(9)?;
+            let (day, month, year) = (
+                date.get(0..2)?.parse().ok()?,
+                date.get(2..4)?.parse().ok()?,
+                date.get(4..6)?.parse::<i32>().ok()?,
+            );
+            // Two digit years roll over with GPS itself, which started in 1980
+            let year = if year < 80 { 2000 + year } else { 1900 + year };
+            NaiveDate::from_ymd_opt(year, month, day)
+        } else if self.is("ZDA") {
+            NaiveDate::from_ymd_opt(
+                self.field(4)?.parse().ok()?,
+                self.field(3)?.parse().ok()?,
+                self.field(2)?.parse().ok()?,
+            )
+        } else {
+            None
+        }
+    }
+}
+
+/// Field of a sentence read as a channel, such as `GGA.altitude` or `GSA.15`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NmeaField {
+    sentence: String,
+    /// Index counted from 1 after the address
+    index: usize,
+    conversion: Conversion,
+    /// Name or index as given
+    name: String,
+}
+
+impl NmeaField {
+    /// Text of the field, if the sentence is of this field's type and the field is not empty
+    pub fn text<'a>(&self, sentence: &Sentence<'a>) -> Option<&'a str> {
+        if !sentence.is(&self.sentence) {
+            return None;
+        }
+
+        sentence.field(self.index)
+    }
+
+    /// Reads the field from a sentence, or None if it is missing or not a number.
+    pub fn read(&self, sentence: &Sentence) -> Option<f64> {
+        let text = self.text(sentence)?;
+        // Hemispheres and directions are often left empty along with their value, so default to positive
+        let negated = |index| matches!(sentence.field(index), Some("S" | "W"));
+
+        match self.conversion {
+            Conversion::Number => text.parse().ok(),
+            Conversion::Coordinate(hemisphere) => {
+                let value: f64 = text.parse().ok()?;
+                let degrees = (value / 100.0).trunc();
+                let degrees = degrees + (value - degrees * 100.0) / 60.0;
+                Some(if negated(hemisphere) {
+                    -degrees
+                } else {
+                    degrees
+                })
+            }
+            Conversion::Direction(direction) => {
+                let value: f64 = text.parse().ok()?;
+                Some(if negated(direction) { -value } else { value })
+            }
+            Conversion::Status => match text {
+                "A" => Some(1.0),
+                "V" => Some(0.0),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl Display for NmeaField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.sentence, self.name)
+    }
+}
+
+impl FromStr for NmeaField {
+    type Err = String;
+
+    /// Parses a sentence type and a field name or index, such as `GGA.lat`, `GPRMC.speed` or `XDR.2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sentence, name) = s
+            .split_once('.')
+            .ok_or_else(|| format!("`{s}` is not of the form SENTENCE.FIELD"))?;
+        if sentence.len() < 3 || !sentence.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("`{sentence}` is not a sentence type"));
+        }
+
+        // Named fields are found by sentence type, so they also work when restricted to a talker
+        let sentence_type = &sentence[sentence.len() - 3..];
+        let (index, conversion) = match NAMED_FIELDS
+            .iter()
+            .find(|(t, n, _, _)| *t == sentence_type && *n == name)
+        {
+            Some((_, _, index, conversion)) => (*index, *conversion),
+            None => match name.parse::<usize>() {
+                Ok(index) if index > 0 => (index, Conversion::Number),
+                _ => {
+                    let names: Vec<&str> = NAMED_FIELDS
+                        .iter()
+                        .filter(|(t, _, _, _)| *t == sentence_type)
+                        .map(|(_, n, _, _)| *n)
+                        .collect();
+                    return Err(if names.is_empty() {
+                        format!("`{name}` is not a field index counted from 1")
+                    } else {
+                        format!(
+                            "`{name}` is not a field index counted from 1, or one of {}",
+                            names.join(", ")
+                        )
+                    });
+                }
+            },
+        };
+
+        Ok(Self {
+            sentence: sentence.to_string(),
+            index,
+            conversion,
+            name: name.to_string(),
+        })
+    }
+}
+
+/// Turns the times of sentences into timestamps, using the date of the latest RMC or ZDA sentence
+#[derive(Default)]
+pub struct SentenceClock {
+    date: Option<NaiveDate>,
+    /// Latest timestamp, which sentences without a time share
+    last: Option<DateTime<Utc>>,
+}
+
+impl SentenceClock {
+    /// Timestamp of a sentence read at `arrival`.
+    pub fn stamp(&mut self, sentence: &Sentence, arrival: DateTime<Utc>) -> DateTime<Utc> {
+        let date = sentence.date();
+        if date.is_some() {
+            self.date = date;
+        }
+
+        let reference = self.last.unwrap_or(arrival);
+        let time = match sentence.time() {
+            Some(time) => time,
+            None => return reference,
+        };
+
+        let day = self.date.unwrap_or_else(|| reference.date_naive());
+        let mut stamp = Utc.from_utc_datetime(&day.and_time(time));
+        // Without its own date, the time may have passed midnight since the date was known
+        if date.is_none() {
+            if stamp - reference > Duration::hours(12) {
+                stamp -= Duration::days(1);
+            } else if reference - stamp > Duration::hours(12) {
+                stamp += Duration::days(1);
+            }
+            if self.date.is_some() {
+                self.date = Some(stamp.date_naive());
+            }
+        }
+
+        self.last = Some(stamp);
+        stamp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A classic GGA example with a valid checksum
+    const GGA: &str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+
+    /// Wraps a body in `$` and its checksum.
+    fn sentence(body: &str) -> String {
+        format!("${body}*{:02X}", body.bytes().fold(0, |sum, b| sum ^ b))
+    }
+
+    #[test]
+    fn checksum() {
+        assert!(Sentence::parse(GGA).is_some());
+        assert!(Sentence::parse(&format!("{GGA}\r\n")).is_some());
+        assert!(Sentence::parse(&GGA.replace("545.4", "545.5")).is_none());
+        assert!(Sentence::parse(&GGA.replace("*47", "*48")).is_none());
+        assert!(Sentence::parse(&GGA.replace("*47", "")).is_none());
+    }
+
+    #[test]
+    fn read_fields() {
+        let gga = Sentence::parse(GGA).unwrap();
+        let read = |field: &str| field.parse::<NmeaField>().unwrap().read(&gga);
+        assert_eq!(read("GGA.altitude"), Some(545.4));
+        assert_eq!(read("GGA.satellites"), Some(8.0));
+        assert_eq!(read("RMC.speed"), None);
+        assert!((read("GGA.lat").unwrap() - 48.1173).abs() < 1e-9);
+    }
+
+    #[test]
+    fn midnight_rollover() {
+        let arrival = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 5).unwrap();
+        let mut clock = SentenceClock::default();
+
+        let rmc = sentence("GPRMC,235959.50,A,4807.038,N,01131.000,E,12.0,90.0,010124,1.2,W,A");
+        let stamp = clock.stamp(&Sentence::parse(&rmc).unwrap(), arrival);
+        assert_eq!(
+            stamp,
+            Utc.with_ymd_and_hms(2024, 1, 1, 23, 59, 59).unwrap() + Duration::milliseconds(500)
+        );
+
+        // A GGA without a date passes midnight after the RMC that gave the date
+        let gga = sentence("GPGGA,000000.50,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,");
+        let stamp = clock.stamp(&Sentence::parse(&gga).unwrap(), arrival);
+        assert_eq!(
+            stamp,
+            Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap() + Duration::milliseconds(500)
+        );
+
+        // Sentences without a time share the latest timestamp
+        let vtg = sentence("GPVTG,90.0,T,,M,12.0,N,22.2,K,A");
+        assert_eq!(clock.stamp(&Sentence::parse(&vtg).unwrap(), arrival), stamp);
+    }
+}
@@ -0,0 +1,292 @@
+//! Parsing of captured numbers in the formats devices print them in
+
+use std::str::FromStr;
+
+/// SI prefixes accepted as suffixes, with their power of ten
+const SI_PREFIXES: [(char, i32); 10] = [
+    ('p', -12),
+    ('n', -9),
+    ('µ', -6),
+    ('μ', -6),
+    ('u', -6),
+    ('m', -3),
+    ('k', 3),
+    ('M', 6),
+    ('G', 9),
+    ('T', 12),
+];
+
+/// What to do with readings of `inf` or `nan`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NonFinite {
+    /// Keep them as readings
+    #[default]
+    Keep,
+    /// Count them as unparseable
+    Drop,
+}
+
+/// Base integers are written in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Radix {
+    /// Detected from a `0x`, `0b` or `0o` prefix, otherwise decimal
+    #[default]
+    Auto,
+    Binary,
+    Octal,
+    Decimal,
+    Hex,
+}
+
+impl Radix {
+    /// Prefix of the radix and its base
+    fn prefixes(&self) -> &'static [(&'static str, u32)] {
+        match self {
+            Radix::Auto => &[
+                ("0x", 16),
+                ("0X", 16),
+                ("0b", 2),
+                ("0B", 2),
+                ("0o", 8),
+                ("0O", 8),
+            ],
+            Radix::Binary => &[("0b", 2), ("0B", 2)],
+            Radix::Octal => &[("0o", 8), ("0O", 8)],
+            Radix::Decimal => &[],
+            Radix::Hex => &[("0x", 16), ("0X", 16)],
+        }
+    }
+
+    /// Base of numbers without a prefix
+    fn base(&self) -> u32 {
+        match self {
+            Radix::Auto | Radix::Decimal => 10,
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Hex => 16,
+        }
+    }
+}
+
+/// How integers such as register dumps are written
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntegerFormat {
+    pub radix: Radix,
+    /// Reinterprets unsigned values as signed two's complement of this many bits
+    pub signed_bits: Option<u32>,
+}
+
+impl IntegerFormat {
+    /// Parses captured text as an integer, or None if it is not one or does not fit in the signed bits.
+    pub fn parse(&self, text: &str) -> Option<f64> {
+        let text = text.trim();
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+
+        let (digits, base) = self
+            .radix
+            .prefixes()
+            .iter()
+            .find_map(|(prefix, base)| Some((text.strip_prefix(prefix)?, *base)))
+            .unwrap_or((text, self.radix.base()));
+        // Long registers are often grouped, such as `0x1F3A_0000`
+        let digits = digits.replace('_', "");
+        // The sign was already taken, and from_str_radix would accept another
+        if digits.starts_with(['+', '-']) {
+            return None;
+        }
+        let magnitude = u64::from_str_radix(&digits, base).ok()? as i128;
+
+        self.convert(if negative { -magnitude } else { magnitude })
+    }
+
+    /// Reinterprets an integer as signed, or None if it does not fit in the signed bits.
+    pub fn convert(&self, mut value: i128) -> Option<f64> {
+        if let Some(bits) = self.signed_bits {
+            // Negative values are already signed, so only need to fit, down to -2^(bits - 1)
+            if value < 0 {
+                if -value > 1 << (bits - 1) {
+                    return None;
+                }
+            } else if value >= 1 << bits {
+                return None;
+            } else if value >= 1 << (bits - 1) {
+                value -= 1 << bits;
+            }
+        }
+
+        Some(value as f64)
+    }
+}
+
+impl FromStr for IntegerFormat {
+    type Err = String;
+
+    /// Parses a radix of `auto`, `bin`, `oct`, `dec` or `hex`, optionally followed by `,signed=BITS`,
+    /// such as `hex,signed=16`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (radix, signed) = match s.split_once(',') {
+            Some((radix, signed)) => (radix.trim(), Some(signed.trim())),
+            None => (s.trim(), None),
+        };
+
+        let radix = match radix {
+            "auto" => Radix::Auto,
+            "bin" => Radix::Binary,
+            "oct" => Radix::Octal,
+            "dec" => Radix::Decimal,
+            "hex" => Radix::Hex,
+            _ => return Err(format!("`{radix}` is not a radix")),
+        };
+
+        let signed_bits = signed
+            .map(|signed| {
+                signed
+                    .strip_prefix("signed=")
+                    .and_then(|bits| bits.parse::<u32>().ok())
+                    .filter(|bits| (1..=64).contains(bits))
+                    .ok_or_else(|| {
+                        format!("`{signed}` is not of the form signed=BITS, with 1 to 64 bits")
+                    })
+            })
+            .transpose()?;
+
+        Ok(Self { radix, signed_bits })
+    }
+}
+
+/// How the numbers captured by a channel are written.
+///
+/// The default accepts what `f64::from_str` does: an optional sign, a decimal point, an exponent, and
+/// `inf` or `nan`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NumberFormat {
+    /// Uses `,` as the decimal point, as German and French locales do
+    pub decimal_comma: bool,
+    /// Skips thousands separators, which are `,` or `.` (whichever is not the decimal point), `_`, `'`
+    /// and spaces
+    pub thousands: bool,
+    /// Accepts an SI prefix after the number, such as `4.7k` or `10µ`
+    pub si: bool,
+    pub non_finite: NonFinite,
+    /// Parses integers in this format instead of decimal numbers if set
+    pub integer: Option<IntegerFormat>,
+}
+
+impl NumberFormat {
+    /// Parses captured text as a number, or None if it is not one in this format.
+    pub fn parse(&self, text: &str) -> Option<f64> {
+        if let Some(integer) = &self.integer {
+            return integer.parse(text);
+        }
+
+        let mut text = text.trim();
+
+        let mut exponent = 0;
+        if self.si {
+            if let Some((prefix, prefix_exponent)) =
+                SI_PREFIXES.iter().find(|(p, _)| text.ends_with(*p))
+            {
+                let number = &text[..text.len() - prefix.len_utf8()];
+                // A trailing letter is only a prefix after a digit, so `nan` is not read as nano
+                if number
+                    .trim_end()
+                    .ends_with(|c: char| c.is_ascii_digit() || c == '.' || c == ',')
+                {
+                    text = number.trim_end();
+                    exponent = *prefix_exponent;
+                }
+            }
+        }
+
+        let decimal = if self.decimal_comma { ',' } else { '.' };
+        let mut number = String::with_capacity(text.len());
+        for c in text.chars() {
+            if c == decimal {
+                number.push('.');
+            } else if self.thousands && is_thousands_separator(c, decimal) {
+                continue;
+            } else {
+                number.push(c);
+            }
+        }
+
+        // Divide for small prefixes, as 1e-6 and the like are not exact
+        let value = number.parse::<f64>().ok()?;
+        let value = if exponent < 0 {
+            value / 10f64.powi(-exponent)
+        } else {
+            value * 10f64.powi(exponent)
+        };
+        self.float(value)
+    }
+
+    /// Reads an integer item of a record, reinterpreting it if an integer format is set.
+    pub fn integer(&self, value: i128) -> Option<f64> {
+        match &self.integer {
+            Some(integer) => integer.convert(value),
+            None => Some(value as f64),
+        }
+    }
+
+    /// Reads a float, or None if it is not finite and those are dropped.
+    pub fn float(&self, value: f64) -> Option<f64> {
+        if !value.is_finite() && self.non_finite == NonFinite::Drop {
+            return None;
+        }
+
+        Some(value)
+    }
+}
+
+/// Whether a character groups thousands when `decimal` is the decimal point.
+fn is_thousands_separator(c: char, decimal: char) -> bool {
+    match c {
+        ',' | '.' => c != decimal,
+        '_' | '\'' | ' ' | '\u{a0}' | '\u{202f}' => true,
+        _ => false,
+    }
+}
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    /// Parses options separated by commas, such as `decimal-comma,thousands,si,non-finite=drop`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut format = Self::default();
+
+        for option in s.split(',').map(str::trim) {
+            match option {
+                "decimal-comma" => format.decimal_comma = true,
+                "thousands" => format.thousands = true,
+                "si" => format.si = true,
+                "non-finite=keep" => format.non_finite = NonFinite::Keep,
+                "non-finite=drop" => format.non_finite = NonFinite::Drop,
+                _ => return Err(format!("`{option}` is not a number format option")),
+            }
+        }
+
+        Ok(format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_bits_fit() {
+        let format: IntegerFormat = "dec,signed=8".parse().unwrap();
+        assert_eq!(format.parse("255"), Some(-1.0));
+        assert_eq!(format.parse("256"), None);
+        assert_eq!(format.parse("-128"), Some(-128.0));
+        assert_eq!(format.parse("-129"), None);
+        assert_eq!(format.parse("-300"), None);
+        assert_eq!(format.parse("-+5"), None);
+        assert_eq!(format.parse("++5"), None);
+        assert_eq!(format.parse("+5"), Some(5.0));
+        assert_eq!("hex".parse::<IntegerFormat>().unwrap().parse("0x+5"), None);
+    }
+}
@@ -0,0 +1,233 @@
+//! Self-describing binary records, such as MessagePack or CBOR maps, and selecting readings from them by
+//! key path
+
+use crate::{cbor, msgpack};
+use clap::ValueEnum;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Longest record kept while waiting for the rest of it, so a corrupted length does not stall decoding
+const MAX_RECORD_LEN: usize = 1 << 20;
+
+/// Deepest nesting of arrays and maps decoded
+pub const MAX_DEPTH: usize = 64;
+
+/// Encoding of the records on stdin
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RecordFormat {
+    Msgpack,
+    Cbor,
+}
+
+/// A decoded item of a record
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+    Null,
+    Bool(bool),
+    /// Wide enough for both unsigned 64 bit and CBOR's negative 64 bit integers
+    Int(i128),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    Array(Vec<Item>),
+    /// Entries in the order they were encoded
+    Map(Vec<(Item, Item)>),
+}
+
+impl Item {
+    /// Follows a key path down through maps and arrays.
+    pub fn get(&self, path: &KeyPath) -> Option<&Item> {
+        path.0.iter().try_fold(self, |item, key| match item {
+            Item::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| match k {
+                    Item::Str(k) => k == key,
+                    // CBOR maps often use integer keys to save space
+                    Item::Int(k) => key.parse::<i128>() == Ok(*k),
+                    _ => false,
+                })
+                .map(|(_, v)| v),
+            Item::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+    }
+}
+
+impl Display for Item {
+    /// Writes the item much like JSON, for showing records in the console.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Item::Null => write!(f, "null"),
+            Item::Bool(b) => write!(f, "{b}"),
+            Item::Int(i) => write!(f, "{i}"),
+            Item::Float(x) => write!(f, "{x:?}"),
+            Item::Str(s) => write!(f, "{s:?}"),
+            Item::Bytes(bytes) => {
+                write!(f, "h'")?;
+                bytes.iter().try_for_each(|b| write!(f, "{b:02x}"))?;
+                write!(f, "'")
+            }
+            Item::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Item::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Path to an item in a record, such as `imu.accel.0`.
+///
+/// Each key names an entry of a map, by its string or integer key, or an index of an array.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyPath(Vec<String>);
+
+impl Display for KeyPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.join("."))
+    }
+}
+
+impl FromStr for KeyPath {
+    type Err = String;
+
+    /// Parses keys separated by `.`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys: Vec<String> = s.split('.').map(str::to_string).collect();
+
+        if keys.iter().any(String::is_empty) {
+            return Err(format!("`{s}` has an empty key"));
+        }
+
+        Ok(Self(keys))
+    }
+}
+
+/// Why an item could not be decoded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// More bytes are needed
+    Incomplete,
+    /// The bytes are not a valid item
+    Invalid,
+}
+
+/// Cursor over bytes being decoded
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Takes the next `n` bytes.
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        if n > MAX_RECORD_LEN {
+            return Err(DecodeError::Invalid);
+        }
+
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or(DecodeError::Incomplete)?;
+        self.pos += n;
+
+        Ok(bytes)
+    }
+
+    pub fn byte(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Reads a big endian unsigned integer of `n` bytes.
+    pub fn uint(&mut self, n: usize) -> Result<u64, DecodeError> {
+        Ok(self
+            .take(n)?
+            .iter()
+            .fold(0, |value, byte| (value << 8) | *byte as u64))
+    }
+
+    /// Looks at the next byte without taking it.
+    pub fn peek(&self) -> Result<u8, DecodeError> {
+        self.bytes
+            .get(self.pos)
+            .copied()
+            .ok_or(DecodeError::Incomplete)
+    }
+
+    /// Reads a UTF-8 string of `n` bytes.
+    pub fn str(&mut self, n: usize) -> Result<String, DecodeError> {
+        String::from_utf8(self.take(n)?.to_vec()).map_err(|_| DecodeError::Invalid)
+    }
+}
+
+/// Splits a byte stream into records
+pub struct RecordDecoder {
+    format: RecordFormat,
+    /// Bytes read but not yet decoded
+    buffer: Vec<u8>,
+    /// Whether bytes are being skipped after a corrupted record, so the record is counted once
+    skipping: bool,
+}
+
+impl RecordDecoder {
+    pub fn new(format: RecordFormat) -> Self {
+        Self {
+            format,
+            buffer: Vec::new(),
+            skipping: false,
+        }
+    }
+
+    /// Adds bytes read from the stream, returning each whole record, or None for each corrupted one.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Option<Item>> {
+        self.buffer.extend_from_slice(bytes);
+        let mut records = Vec::new();
+
+        while !self.buffer.is_empty() {
+            let mut reader = Reader::new(&self.buffer);
+            let item = match self.format {
+                RecordFormat::Msgpack => msgpack::decode(&mut reader, 0),
+                RecordFormat::Cbor => cbor::decode(&mut reader, 0),
+            };
+
+            match item {
+                Ok(item) => {
+                    let len = reader.pos;
+                    self.buffer.drain(..len);
+                    records.push(Some(item));
+                    self.skipping = false;
+                }
+                Err(DecodeError::Incomplete) if self.buffer.len() <= MAX_RECORD_LEN => break,
+                // Skip a byte at a time until a record decodes again
+                Err(_) => {
+                    self.buffer.drain(..1);
+                    if !self.skipping {
+                        records.push(None);
+                    }
+                    self.skipping = true;
+                }
+            }
+        }
+
+        records
+    }
+}
@@ -0,0 +1,242 @@
+//! Panel for adding, editing and removing channel regexes while running
+
+use crate::console::InputLines;
+use crate::extractor::{Channel, Config, Source};
+use crate::interface::{Message, WARNING};
+use crate::number::NumberFormat;
+use iced::widget::{row, Button, Column, Text, TextInput};
+use iced::{Element, Length};
+use regex::Regex;
+use std::sync::{Arc, RwLock};
+
+/// Number of parsed values shown in a preview
+const PREVIEW_VALUES: usize = 5;
+
+/// A regex channel being edited
+struct Draft {
+    channel: usize,
+    name: String,
+    /// Pattern in use by the extractor
+    applied: String,
+    /// Pattern being typed
+    pattern: String,
+}
+
+/// Collapsible panel to change the regex channels of the shared extractor config
+pub struct RegexEditor {
+    config: Arc<RwLock<Config>>,
+    /// Whether stdin is matched by regexes at all, else the panel is hidden
+    available: bool,
+    /// Recent raw input to preview patterns against
+    lines: Arc<InputLines>,
+    drafts: Vec<Draft>,
+    /// Pattern for a new channel
+    new_pattern: String,
+    expanded: bool,
+}
+
+impl RegexEditor {
+    pub fn new(config: Arc<RwLock<Config>>, lines: Arc<InputLines>) -> Self {
+        let available = config.read().unwrap().matches_regexes();
+        let drafts = config
+            .read()
+            .unwrap()
+            .channels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match &c.source {
+                Source::Regex(regex) => Some(Draft {
+                    channel: i,
+                    name: c.label(),
+                    applied: regex.as_str().to_string(),
+                    pattern: regex.as_str().to_string(),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            config,
+            available,
+            lines,
+            drafts,
+            new_pattern: String::new(),
+            expanded: false,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.expanded = !self.expanded;
+    }
+
+    pub fn set_pattern(&mut self, draft: usize, pattern: String) {
+        self.drafts[draft].pattern = pattern;
+    }
+
+    pub fn set_new_pattern(&mut self, pattern: String) {
+        self.new_pattern = pattern;
+    }
+
+    /// Swaps the regex of a channel for its draft pattern, returning false if the pattern is invalid.
+    pub fn apply(&mut self, draft: usize) -> bool {
+        let draft = &mut self.drafts[draft];
+        let regex = match validate(&draft.pattern) {
+            Ok(regex) => regex,
+            Err(_) => return false,
+        };
+
+        self.config.write().unwrap().channels[draft.channel].source = Source::Regex(regex);
+        draft.applied = draft.pattern.clone();
+        log::info!("{} now matches `{}`", draft.name, draft.applied);
+
+        true
+    }
+
+    /// Stops matching a channel, returning the channel removed.
+    ///
+    /// The channel keeps its index so later channels and the CSV are not renumbered.
+    pub fn remove(&mut self, draft: usize) -> usize {
+        let draft = self.drafts.remove(draft);
+
+        let mut config = self.config.write().unwrap();
+        config.channels[draft.channel].source = Source::Removed;
+        config.channels[draft.channel].hidden = true;
+        log::info!("Removed {}", draft.name);
+
+        draft.channel
+    }
+
+    /// Adds a channel matching the new pattern after all others, returning its index and name.
+    ///
+    /// Nothing is added when stdin is not matched by regexes, since the channel would never read anything.
+    pub fn add(&mut self) -> Option<(usize, String)> {
+        if !self.available {
+            return None;
+        }
+        let regex = validate(&self.new_pattern).ok()?;
+
+        let mut config = self.config.write().unwrap();
+        let channel = config.channels.len();
+        config.channels.push(Channel::regex(channel, regex));
+        let name = config.channels[channel].label();
+        log::info!("Added {name} matching `{}`", self.new_pattern);
+
+        self.drafts.push(Draft {
+            channel,
+            name: name.clone(),
+            applied: self.new_pattern.clone(),
+            pattern: std::mem::take(&mut self.new_pattern),
+        });
+
+        Some((channel, name))
+    }
+
+    /// Describes what a pattern matches in the recent input, parsing numbers in a channel's format, or
+    /// why it is invalid.
+    fn preview(&self, pattern: &str, format: &NumberFormat) -> Result<String, String> {
+        let regex = validate(pattern)?;
+        let input = self.lines.snapshot().join("\n");
+
+        let mut matches = 0;
+        let mut unparseable = 0;
+        let mut values = Vec::new();
+        for captures in regex.captures_iter(&input) {
+            matches += 1;
+            match format.parse(captures.get(1).map_or("", |m| m.as_str())) {
+                Some(v) => values.push(v),
+                None => unparseable += 1,
+            }
+        }
+
+        let latest: Vec<String> = values
+            .iter()
+            .rev()
+            .take(PREVIEW_VALUES)
+            .map(f64::to_string)
+            .collect();
+
+        match (matches, unparseable) {
+            (0, _) => Err("No matches in recent input".to_string()),
+            (_, 0) => Ok(format!(
+                "{matches} matches in recent input, latest: {}",
+                latest.join(", ")
+            )),
+            _ => Err(format!(
+                "{matches} matches in recent input, {unparseable} not numbers"
+            )),
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        if !self.available {
+            return Column::new().into();
+        }
+
+        let toggle = Button::new(if self.expanded {
+            "Hide regex settings"
+        } else {
+            "Show regex settings"
+        })
+        .on_press(Message::ToggleRegexEditor);
+
+        let mut panel = Column::new().width(Length::Fill).spacing(5).push(toggle);
+        if !self.expanded {
+            return panel.into();
+        }
+
+        let config = self.config.read().unwrap();
+        let preview = |pattern: &str, format: &NumberFormat| {
+            let text = match self.preview(pattern, format) {
+                Ok(preview) => Text::new(preview),
+                Err(e) => Text::new(e).style(WARNING),
+            };
+            text.size(14)
+        };
+
+        for (i, draft) in self.drafts.iter().enumerate() {
+            let mut apply = Button::new("Apply");
+            if draft.pattern != draft.applied && validate(&draft.pattern).is_ok() {
+                apply = apply.on_press(Message::RegexApplied(i));
+            }
+
+            panel = panel
+                .push(row![
+                    Text::new(format!("{}: ", draft.name)),
+                    TextInput::new("Regex", &draft.pattern, move |p| Message::RegexEdited(i, p)),
+                    apply,
+                    Button::new("Remove").on_press(Message::RegexRemoved(i)),
+                ])
+                .push(preview(
+                    &draft.pattern,
+                    &config.channels[draft.channel].format,
+                ));
+        }
+
+        let mut add = Button::new("Add channel");
+        if validate(&self.new_pattern).is_ok() {
+            add = add.on_press(Message::RegexAdded);
+        }
+
+        panel = panel.push(row![
+            Text::new("New channel: "),
+            TextInput::new("Regex", &self.new_pattern, Message::NewRegexEdited),
+            add,
+        ]);
+        if !self.new_pattern.is_empty() {
+            panel = panel.push(preview(&self.new_pattern, &NumberFormat::default()));
+        }
+
+        panel.into()
+    }
+}
+
+/// Compiles a pattern, checking it has a capture group for the reading.
+fn validate(pattern: &str) -> Result<Regex, String> {
+    let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+
+    if regex.captures_len() < 2 {
+        return Err("Regex needs a capture group around the number".to_string());
+    }
+
+    Ok(regex)
+}
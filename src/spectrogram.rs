@@ -0,0 +1,267 @@
+//! Scrolling spectrogram of a single channel
+
+use crate::colormap::Colormap;
+use crate::dsp::{amplitude_spectrum, Magnitude, Window};
+use crate::interface::{Message, SignalChart, FONT_BOLD, FONT_REGULAR};
+use iced::widget::{canvas::Cache, canvas::Frame, canvas::Geometry, row, Column, PickList, Text};
+use iced::{Element, Length, Size};
+use plotters::prelude::ChartBuilder;
+use plotters_iced::plotters_backend::DrawingBackend;
+use plotters_iced::{Chart, ChartWidget};
+use std::collections::VecDeque;
+
+/// Number of frequency rows each column is pooled into, to keep drawing cheap.
+const ROWS: usize = 64;
+
+/// Settings for the spectrogram panel
+#[derive(Copy, Clone, Debug)]
+pub struct SpectrogramSettings {
+    /// Channel to analyse
+    pub channel: usize,
+    pub colormap: Colormap,
+    pub window: Window,
+    /// How much time the spectrogram scrolls over
+    pub history_ms: u64,
+    /// Time between each column
+    pub hop_ms: u64,
+    /// Amount of data each column's FFT is computed over
+    pub span_ms: u64,
+}
+
+/// A single FFT in the spectrogram
+struct SpectrumColumn {
+    /// Time span this column covers, in the same time base as the signal chart
+    start_ms: i64,
+    end_ms: i64,
+    /// Highest frequency in the column
+    nyquist: f64,
+    /// Magnitude in dB of each evenly spaced row from 0Hz to nyquist
+    rows: Vec<f64>,
+}
+
+/// Widget that displays a time-frequency heatmap of a channel
+pub struct SpectrogramChart {
+    cache: Cache,
+    num_channels: usize,
+    settings: SpectrogramSettings,
+    /// Columns ordered oldest first
+    columns: VecDeque<SpectrumColumn>,
+    /// Channels removed while running, which are no longer listed
+    removed: Vec<usize>,
+}
+
+impl SpectrogramChart {
+    pub fn new(num_channels: usize, settings: SpectrogramSettings) -> Self {
+        Self {
+            cache: Cache::new(),
+            num_channels,
+            settings,
+            columns: VecDeque::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Makes a channel added while running selectable.
+    pub fn add_channel(&mut self) {
+        self.num_channels += 1;
+    }
+
+    /// Stops listing a channel removed while running, clearing the history if it was analysed.
+    pub fn remove_channel(&mut self, channel: usize) {
+        self.removed.push(channel);
+        if channel == self.settings.channel {
+            self.columns.clear();
+            self.cache.clear();
+        }
+    }
+
+    pub const fn channel(&self) -> usize {
+        self.settings.channel
+    }
+
+    /// Changes the analysed channel, which clears the history.
+    pub fn set_channel(&mut self, channel: usize) {
+        self.settings.channel = channel;
+        self.columns.clear();
+        self.cache.clear();
+    }
+
+    pub fn set_colormap(&mut self, colormap: Colormap) {
+        self.settings.colormap = colormap;
+        self.cache.clear();
+    }
+
+    /// Adds a column from the latest readings of the signal chart if a hop has passed since the last.
+    pub fn update(&mut self, signal: &SignalChart) {
+        let now = signal.latest_ms();
+        let start = match self.columns.back() {
+            Some(last) if now - last.end_ms < self.settings.hop_ms as i64 => return,
+            // Keep columns contiguous unless data stopped for a while
+            Some(last) if now - last.end_ms < 2 * self.settings.hop_ms as i64 => last.end_ms,
+            _ => now - self.settings.hop_ms as i64,
+        };
+
+        let samples = signal.recent_samples(self.settings.channel, self.settings.span_ms);
+        let bins = match amplitude_spectrum(&samples, self.settings.window) {
+            Some(bins) => bins,
+            None => return,
+        };
+        let nyquist = bins.last().map_or(0.0, |(hz, _)| *hz);
+
+        // Pool bins into rows, keeping the peak of each so narrow tones are not lost
+        let mut rows = vec![f64::NEG_INFINITY; ROWS];
+        for (hz, amp) in bins {
+            let row = ((hz / nyquist) * ROWS as f64) as usize;
+            let row = row.min(ROWS - 1);
+            rows[row] = rows[row].max(Magnitude::Db.scale(amp));
+        }
+
+        // Rows with no bins take the value below them
+        for i in 1..ROWS {
+            if rows[i] == f64::NEG_INFINITY {
+                rows[i] = rows[i - 1];
+            }
+        }
+
+        self.columns.push_back(SpectrumColumn {
+            start_ms: start,
+            end_ms: now,
+            nyquist,
+            rows,
+        });
+
+        // Scroll off old columns
+        while let Some(first) = self.columns.front() {
+            if first.end_ms < now - self.settings.history_ms as i64 {
+                self.columns.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        self.cache.clear();
+    }
+
+    /// Range of dB values currently displayed
+    fn db_range(&self) -> Option<(f64, f64)> {
+        let (low, high) = self
+            .columns
+            .iter()
+            .flat_map(|c| c.rows.iter())
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(*v), hi.max(*v))
+            });
+
+        (low.is_finite() && high.is_finite()).then_some((low, high))
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let channels: Vec<usize> = (0..self.num_channels)
+            .filter(|c| !self.removed.contains(c))
+            .collect();
+        let range = self.db_range().map_or_else(String::new, |(low, high)| {
+            format!(" Range: {:.0}dB to {:.0}dB", low, high)
+        });
+
+        Column::new()
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .spacing(5)
+            .push(row![
+                Text::new("Channel: "),
+                PickList::new(
+                    channels,
+                    Some(self.settings.channel),
+                    Message::SpectrogramChannel
+                ),
+                Text::new(" Colormap: "),
+                PickList::new(
+                    &Colormap::ALL[..],
+                    Some(self.settings.colormap),
+                    Message::SpectrogramColormap
+                ),
+                Text::new(range),
+            ])
+            .push(ChartWidget::new(self).height(Length::Fill).resolve_font(
+                |_, style| match style {
+                    plotters_iced::plotters_backend::FontStyle::Bold => FONT_BOLD,
+                    _ => FONT_REGULAR,
+                },
+            ))
+            .into()
+    }
+}
+
+impl Chart<Message> for SpectrogramChart {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut chart: ChartBuilder<DB>) {
+        use plotters::{prelude::*, style::Color};
+
+        let latest = self.columns.back().map_or(0, |c| c.end_ms);
+        let oldest = latest - self.settings.history_ms as i64;
+        let max_hz = self
+            .columns
+            .iter()
+            .fold(0.0f64, |hz, c| hz.max(c.nyquist))
+            .max(f64::EPSILON);
+
+        let mut chart = chart
+            .x_label_area_size(28)
+            .y_label_area_size(28)
+            .margin(20)
+            .build_cartesian_2d(oldest..latest, 0.0..max_hz)
+            .expect("failed to build chart");
+
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .axis_style(ShapeStyle::from(BLUE.mix(0.80)).stroke_width(1))
+            .y_labels(10)
+            .y_label_style(
+                ("sans-serif", 15)
+                    .into_font()
+                    .color(&BLUE.mix(0.80))
+                    .transform(FontTransform::Rotate90),
+            )
+            .y_label_formatter(&|hz| format!("{:.1}Hz", hz))
+            .x_label_formatter(&|d| format!("{}ms", d))
+            .x_labels(10)
+            .x_label_style(
+                ("sans-serif", 15)
+                    .into_font()
+                    .color(&BLUE.mix(0.80))
+                    .transform(FontTransform::Rotate90),
+            )
+            .draw()
+            .expect("failed to draw chart mesh");
+
+        let (low, high) = match self.db_range() {
+            Some(range) => range,
+            None => return,
+        };
+
+        let colormap = self.settings.colormap;
+        chart
+            .draw_series(self.columns.iter().flat_map(|c| {
+                let row_hz = c.nyquist / ROWS as f64;
+
+                c.rows.iter().enumerate().map(move |(i, db)| {
+                    Rectangle::new(
+                        [
+                            (c.start_ms.max(oldest), i as f64 * row_hz),
+                            (c.end_ms, (i + 1) as f64 * row_hz),
+                        ],
+                        colormap.color_in(*db, low, high).filled(),
+                    )
+                })
+            }))
+            .expect("failed to draw spectrogram");
+    }
+
+    #[inline]
+    fn draw<F: Fn(&mut Frame)>(&self, bounds: Size, draw_fn: F) -> Geometry {
+        self.cache.draw(bounds, draw_fn)
+    }
+}
@@ -0,0 +1,191 @@
+//! Live frequency spectrum of a single channel
+
+use crate::color_table::get_color_for_channels;
+use crate::dsp::{amplitude_spectrum, Magnitude, Window};
+use crate::interface::{Message, SignalChart, FONT_BOLD, FONT_REGULAR};
+use iced::widget::{canvas::Cache, canvas::Frame, canvas::Geometry, row, Column, PickList, Text};
+use iced::{Element, Length, Size};
+use plotters::prelude::ChartBuilder;
+use plotters::style::RGBColor;
+use plotters_iced::plotters_backend::DrawingBackend;
+use plotters_iced::{Chart, ChartWidget};
+
+/// Settings for the spectrum panel
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SpectrumSettings {
+    /// Channel to analyse
+    pub channel: usize,
+    pub window: Window,
+    pub magnitude: Magnitude,
+}
+
+/// Widget that displays the FFT of the visible window of a channel
+pub struct SpectrumChart {
+    cache: Cache,
+    /// Color for each channel
+    colors: Vec<RGBColor>,
+    /// Channel being analysed
+    channel: usize,
+    window: Window,
+    magnitude: Magnitude,
+    /// Latest spectrum as (Hz, linear amplitude)
+    bins: Vec<(f64, f64)>,
+    /// Channels removed while running, which are no longer listed
+    removed: Vec<usize>,
+}
+
+impl SpectrumChart {
+    pub fn new(num_channels: usize, settings: SpectrumSettings) -> Self {
+        Self {
+            cache: Cache::new(),
+            colors: get_color_for_channels(num_channels),
+            channel: settings.channel,
+            window: settings.window,
+            magnitude: settings.magnitude,
+            bins: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Makes a channel added while running selectable.
+    pub fn add_channel(&mut self) {
+        self.colors = get_color_for_channels(self.colors.len() + 1);
+    }
+
+    /// Stops listing a channel removed while running, emptying the spectrum if it was analysed.
+    pub fn remove_channel(&mut self, channel: usize, signal: &SignalChart) {
+        self.removed.push(channel);
+        if channel == self.channel {
+            self.update(signal);
+        }
+    }
+
+    pub const fn channel(&self) -> usize {
+        self.channel
+    }
+
+    pub fn set_channel(&mut self, channel: usize, signal: &SignalChart) {
+        self.channel = channel;
+        self.update(signal);
+    }
+
+    pub fn set_window(&mut self, window: Window, signal: &SignalChart) {
+        self.window = window;
+        self.update(signal);
+    }
+
+    pub fn set_magnitude(&mut self, magnitude: Magnitude) {
+        self.magnitude = magnitude;
+        self.cache.clear();
+    }
+
+    /// Recomputes the spectrum from the visible readings of the signal chart.
+    pub fn update(&mut self, signal: &SignalChart) {
+        let samples = signal.visible_samples(self.channel);
+        self.bins = amplitude_spectrum(&samples, self.window).unwrap_or_default();
+        self.cache.clear();
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let channels: Vec<usize> = (0..self.colors.len())
+            .filter(|c| !self.removed.contains(c))
+            .collect();
+
+        Column::new()
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .spacing(5)
+            .push(row![
+                Text::new("Channel: "),
+                PickList::new(channels, Some(self.channel), Message::SpectrumChannel),
+                Text::new(" Window: "),
+                PickList::new(&Window::ALL[..], Some(self.window), Message::SpectrumWindow),
+                Text::new(" Scale: "),
+                PickList::new(
+                    &Magnitude::ALL[..],
+                    Some(self.magnitude),
+                    Message::SpectrumMagnitude
+                ),
+            ])
+            .push(ChartWidget::new(self).height(Length::Fill).resolve_font(
+                |_, style| match style {
+                    plotters_iced::plotters_backend::FontStyle::Bold => FONT_BOLD,
+                    _ => FONT_REGULAR,
+                },
+            ))
+            .into()
+    }
+}
+
+impl Chart<Message> for SpectrumChart {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut chart: ChartBuilder<DB>) {
+        use plotters::{prelude::*, style::Color};
+
+        let points: Vec<(f64, f64)> = self
+            .bins
+            .iter()
+            .map(|(hz, amp)| (*hz, self.magnitude.scale(*amp)))
+            .collect();
+
+        let max_hz = points.last().map_or(1.0, |(hz, _)| *hz).max(f64::EPSILON);
+        let (low, high) = points
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (_, v)| {
+                (lo.min(*v), hi.max(*v))
+            });
+        let (low, high) = if low.is_finite() && high > low {
+            (low, high)
+        } else {
+            (0.0, 1.0)
+        };
+
+        let mut chart = chart
+            .x_label_area_size(28)
+            .y_label_area_size(28)
+            .margin(20)
+            .build_cartesian_2d(0.0..max_hz, low..high)
+            .expect("failed to build chart");
+
+        chart
+            .configure_mesh()
+            .bold_line_style(BLUE.mix(0.4))
+            .light_line_style(BLUE.mix(0.2))
+            .axis_style(ShapeStyle::from(BLUE.mix(0.80)).stroke_width(1))
+            .y_labels(10)
+            .y_label_style(
+                ("sans-serif", 15)
+                    .into_font()
+                    .color(&BLUE.mix(0.80))
+                    .transform(FontTransform::Rotate90),
+            )
+            .y_label_formatter(&|v| match self.magnitude {
+                Magnitude::Linear => format!("{:.3}", v),
+                Magnitude::Db => format!("{:.0}dB", v),
+            })
+            .x_label_formatter(&|hz| format!("{:.1}Hz", hz))
+            .x_labels(10)
+            .x_label_style(
+                ("sans-serif", 15)
+                    .into_font()
+                    .color(&BLUE.mix(0.80))
+                    .transform(FontTransform::Rotate90),
+            )
+            .draw()
+            .expect("failed to draw chart mesh");
+
+        if !points.is_empty() {
+            let color = self.colors[self.channel];
+
+            chart
+                .draw_series(LineSeries::new(points, color))
+                .expect("failed to draw spectrum");
+        }
+    }
+
+    #[inline]
+    fn draw<F: Fn(&mut Frame)>(&self, bounds: Size, draw_fn: F) -> Geometry {
+        self.cache.draw(bounds, draw_fn)
+    }
+}
@@ -0,0 +1,195 @@
+//! Per-channel summary statistics
+
+use crate::color_table::get_color_for_channels;
+use crate::interface::{Message, SignalChart};
+use iced::widget::{Column, Row, Text};
+use iced::{Color, Element, Length};
+use plotters::style::RGBColor;
+
+/// Running summary statistics of a channel's readings.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Summary {
+    count: usize,
+    min: f64,
+    max: f64,
+    /// Running mean, updated with Welford's algorithm to stay stable over long sessions
+    mean: f64,
+    /// Sum of squared differences from the mean
+    m2: f64,
+    sum_sq: f64,
+    first_s: f64,
+    last_s: f64,
+    last: f64,
+}
+
+impl Summary {
+    /// Summarises `(seconds, value)` samples sorted by time.
+    pub fn from_samples(samples: &[(f64, f64)]) -> Self {
+        let mut summary = Self::default();
+        samples.iter().for_each(|(t, v)| summary.push(*t, *v));
+        summary
+    }
+
+    /// Adds a reading taken at `seconds`. Non-finite readings are ignored.
+    pub fn push(&mut self, seconds: f64, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+            self.first_s = seconds;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.sum_sq += value * value;
+        self.last_s = seconds;
+        self.last = value;
+    }
+
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Latest reading
+    pub fn current(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.last)
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    /// Population standard deviation
+    pub fn std_dev(&self) -> Option<f64> {
+        (self.count > 0).then(|| (self.m2 / self.count as f64).sqrt())
+    }
+
+    pub fn rms(&self) -> Option<f64> {
+        (self.count > 0).then(|| (self.sum_sq / self.count as f64).sqrt())
+    }
+
+    pub fn peak_to_peak(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max - self.min)
+    }
+
+    /// Estimated readings per second, from the time between the first and last reading
+    pub fn sample_rate(&self) -> Option<f64> {
+        let span = self.last_s - self.first_s;
+        (self.count > 1 && span > 0.0).then(|| (self.count - 1) as f64 / span)
+    }
+}
+
+/// Table of statistics for each channel, over both the visible window and the whole session
+pub struct StatsPanel {
+    /// Color for each channel
+    colors: Vec<RGBColor>,
+    /// Name of each channel
+    names: Vec<String>,
+    window: Vec<Summary>,
+    session: Vec<Summary>,
+    /// Channels removed while running, which are no longer shown
+    removed: Vec<usize>,
+}
+
+impl StatsPanel {
+    pub fn new(names: Vec<String>) -> Self {
+        let num_channels = names.len();
+
+        Self {
+            colors: get_color_for_channels(num_channels),
+            names,
+            window: vec![Summary::default(); num_channels],
+            session: vec![Summary::default(); num_channels],
+            removed: Vec::new(),
+        }
+    }
+
+    /// Adds a row for a channel added while running.
+    pub fn add_channel(&mut self, name: String) {
+        self.names.push(name);
+        self.colors = get_color_for_channels(self.names.len());
+        self.window.push(Summary::default());
+        self.session.push(Summary::default());
+    }
+
+    /// Stops showing the row of a channel removed while running.
+    pub fn remove_channel(&mut self, channel: usize) {
+        self.removed.push(channel);
+    }
+
+    /// Adds a reading to the session statistics.
+    pub fn push(&mut self, channel: usize, seconds: f64, value: f64) {
+        self.session[channel].push(seconds, value);
+    }
+
+    /// Recomputes the visible window statistics from the signal chart.
+    pub fn update(&mut self, signal: &SignalChart) {
+        for (i, summary) in self.window.iter_mut().enumerate() {
+            *summary = Summary::from_samples(&signal.visible_samples(i));
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        const HEADERS: [&str; 11] = [
+            "Channel", "Scope", "Current", "Min", "Max", "Mean", "σ", "RMS", "P-P", "Count", "Rate",
+        ];
+
+        let cell = |s: String| Text::new(s).size(16).width(Length::FillPortion(1));
+        let fmt = |v: Option<f64>| v.map_or_else(|| "-".to_string(), |v| format!("{:.4}", v));
+
+        let mut table = Column::new().width(Length::Fill).spacing(2).push(
+            HEADERS.iter().fold(Row::new().spacing(5), |row, h| {
+                row.push(cell(h.to_string()))
+            }),
+        );
+
+        for (i, color) in self.colors.iter().enumerate() {
+            if self.removed.contains(&i) {
+                continue;
+            }
+
+            for (scope, summary) in [("window", &self.window[i]), ("session", &self.session[i])] {
+                table = table.push(
+                    Row::new()
+                        .spacing(5)
+                        .push(
+                            cell(self.names[i].clone())
+                                .style(Color::from_rgb8(color.0, color.1, color.2)),
+                        )
+                        .push(cell(scope.to_string()))
+                        .push(cell(fmt(summary.current())))
+                        .push(cell(fmt(summary.min())))
+                        .push(cell(fmt(summary.max())))
+                        .push(cell(fmt(summary.mean())))
+                        .push(cell(fmt(summary.std_dev())))
+                        .push(cell(fmt(summary.rms())))
+                        .push(cell(fmt(summary.peak_to_peak())))
+                        .push(cell(summary.count().to_string()))
+                        .push(cell(
+                            summary
+                                .sample_rate()
+                                .map_or_else(|| "-".to_string(), |r| format!("{:.1}Hz", r)),
+                        )),
+                );
+            }
+        }
+
+        table.into()
+    }
+}
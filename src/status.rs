@@ -0,0 +1,241 @@
+//! Counters describing the input stream, and the status bar that displays them
+
+use crate::extractor::{Config, Source};
+use crate::interface::{Message, WARNING};
+use iced::widget::{Row, Text};
+use iced::Element;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Counters updated by the extractor as it reads, and read by the gui
+pub struct StreamStatus {
+    bytes: AtomicU64,
+    /// Newline terminated records of text, or decoded binary packets or MessagePack/CBOR records
+    records: AtomicU64,
+    /// Matches of each channel's regex, which grows as channels are added
+    matches: Mutex<Vec<u64>>,
+    /// Captures that could not be parsed as a number
+    unparseable: AtomicU64,
+    /// Binary packets dropped for bad framing, checksum or length, records that could not be decoded, or
+    /// sentences with a bad checksum
+    corrupted: AtomicU64,
+}
+
+impl StreamStatus {
+    pub fn new(num_channels: usize) -> Self {
+        Self {
+            bytes: AtomicU64::new(0),
+            records: AtomicU64::new(0),
+            matches: Mutex::new(vec![0; num_channels]),
+            unparseable: AtomicU64::new(0),
+            corrupted: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a chunk of raw input.
+    pub fn add_input(&self, chunk: &[u8]) {
+        self.bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+    }
+
+    /// Counts the lines of a chunk of text input.
+    pub fn add_lines(&self, chunk: &[u8]) {
+        let lines = chunk.iter().filter(|b| **b == b'\n').count();
+        self.records.fetch_add(lines as u64, Ordering::Relaxed);
+    }
+
+    /// Counts a decoded binary packet or record.
+    pub fn add_record(&self) {
+        self.records.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_match(&self, channel: usize) {
+        let mut matches = self.matches.lock().unwrap();
+        if matches.len() <= channel {
+            matches.resize(channel + 1, 0);
+        }
+        matches[channel] += 1;
+    }
+
+    fn matches(&self, channel: usize) -> u64 {
+        self.matches
+            .lock()
+            .unwrap()
+            .get(channel)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn add_unparseable(&self) {
+        self.unparseable.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_corrupted(&self) {
+        self.corrupted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// Bar along the bottom of the gui describing the state of the input
+pub struct StatusBar {
+    status: Arc<StreamStatus>,
+    /// Name of each channel read from stdin, with its index
+    regex_channels: Vec<(usize, String)>,
+    /// What corrupted input is called, if stdin is decoded as binary packets, records or sentences
+    corrupted: Option<&'static str>,
+    /// What each unit of input counted is called, which is a line of text unless decoding packets
+    records: &'static str,
+    /// Whether readings may be unparseable, which binary packets never are
+    parsed: bool,
+    start: Instant,
+    /// Time and byte count at the last tick, to compute the input rate from
+    last_tick: (Instant, u64),
+    /// Bytes per second over the last tick
+    rate: f64,
+}
+
+impl StatusBar {
+    pub fn new(config: &Config) -> Self {
+        let now = Instant::now();
+
+        Self {
+            status: Arc::new(StreamStatus::new(config.channels.len())),
+            regex_channels: regex_channels(config),
+            corrupted: match (&config.layout, config.records, config.nmea) {
+                (Some(_), _, _) => Some("Corrupted packets"),
+                (_, Some(_), _) => Some("Corrupted records"),
+                (_, _, true) => Some("Corrupted sentences"),
+                _ => None,
+            },
+            records: if config.layout.is_some() {
+                "Packets"
+            } else {
+                "Records"
+            },
+            parsed: config.layout.is_none(),
+            start: now,
+            last_tick: (now, 0),
+            rate: 0.0,
+        }
+    }
+
+    /// Updates the regex channels shown after they are changed.
+    pub fn refresh(&mut self, config: &Config) {
+        self.regex_channels = regex_channels(config);
+    }
+
+    /// Counters to give to the extractor
+    pub fn status(&self) -> Arc<StreamStatus> {
+        self.status.clone()
+    }
+
+    /// Updates the input rate.
+    pub fn tick(&mut self, now: Instant) {
+        let (then, bytes) = self.last_tick;
+        let elapsed = now.saturating_duration_since(then).as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+
+        let current = self.status.bytes();
+        self.rate = (current - bytes) as f64 / elapsed;
+        self.last_tick = (now, current);
+    }
+
+    pub fn view(&self, closed: bool) -> Element<'_, Message> {
+        let status = &self.status;
+        let elapsed = self.start.elapsed().as_secs();
+        let cell = |s: String| Text::new(s).size(16);
+
+        // Stdin cannot be reopened once closed, so there is no reconnecting state
+        let mut bar = Row::new()
+            .spacing(15)
+            .push(if closed {
+                cell("Input: closed".to_string()).style(WARNING)
+            } else {
+                cell("Input: open".to_string())
+            })
+            .push(cell(format!(
+                "Elapsed: {:02}:{:02}:{:02}",
+                elapsed / 3600,
+                elapsed / 60 % 60,
+                elapsed % 60
+            )))
+            .push(cell(format!(
+                "Read: {}",
+                format_bytes(status.bytes() as f64)
+            )))
+            .push(cell(format!(
+                "{}: {}",
+                self.records,
+                status.records.load(Ordering::Relaxed)
+            )))
+            .push(cell(format!(
+                "Rate: {}/s",
+                format_bytes(if closed { 0.0 } else { self.rate })
+            )));
+
+        // Highlight regexes that have not matched anything, as they are likely wrong
+        for (i, name) in &self.regex_channels {
+            let matches = status.matches(*i);
+            let text = cell(format!("{name}: {matches} matches"));
+            bar = bar.push(if matches == 0 {
+                text.style(WARNING)
+            } else {
+                text
+            });
+        }
+
+        let counts = [
+            (self.corrupted, &status.corrupted),
+            (self.parsed.then_some("Unparseable"), &status.unparseable),
+        ];
+        for (label, count) in counts {
+            if let Some(label) = label {
+                let count = count.load(Ordering::Relaxed);
+                let text = cell(format!("{label}: {count}"));
+                bar = bar.push(if count > 0 { text.style(WARNING) } else { text });
+            }
+        }
+
+        bar.into()
+    }
+}
+
+/// Index and label of each channel read from stdin
+fn regex_channels(config: &Config) -> Vec<(usize, String)> {
+    config
+        .channels
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| {
+            c.source.regex().is_some()
+                || matches!(
+                    c.source,
+                    Source::Field(_) | Source::Key(_) | Source::Nmea(_)
+                )
+        })
+        .map(|(i, c)| (i, c.label()))
+        .collect()
+}
+
+/// Formats a number of bytes with a binary prefix.
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{value:.0}{}", UNITS[unit])
+    } else {
+        format!("{value:.1}{}", UNITS[unit])
+    }
+}
@@ -0,0 +1,288 @@
+//! Display transforms that show a channel as its derivative or integral
+
+use crate::extractor::{Config, Data, Source, Value};
+use crate::interface::Message;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use iced::widget::{row, Button, PickList, Text};
+use iced::Element;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// How a channel is shown
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Transform {
+    /// Readings as they are
+    #[default]
+    None,
+    /// Rate of change per second
+    Derivative,
+    /// Running trapezoidal integral over seconds
+    Integral,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 3] = [Transform::None, Transform::Derivative, Transform::Integral];
+
+    /// Suffix added to the channel name in the legend
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Transform::None => "",
+            Transform::Derivative => " (d/dt)",
+            Transform::Integral => " (∫dt)",
+        }
+    }
+}
+
+impl Display for Transform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Transform::None => "none",
+            Transform::Derivative => "derivative",
+            Transform::Integral => "integral",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A transform on a channel, as given on the command line.
+#[derive(Copy, Clone, Debug)]
+pub struct TransformSpec {
+    pub channel: usize,
+    pub transform: Transform,
+}
+
+impl FromStr for TransformSpec {
+    type Err = String;
+
+    /// Parses `CHANNEL:KIND`, such as `0:derivative`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (channel, kind) = s
+            .split_once(':')
+            .ok_or_else(|| format!("`{s}` is not of the form CHANNEL:KIND"))?;
+
+        Ok(Self {
+            channel: channel
+                .trim()
+                .parse()
+                .map_err(|_| format!("`{channel}` is not a channel index"))?,
+            transform: Transform::from_str(kind.trim(), true)?,
+        })
+    }
+}
+
+/// Running state of a derivative or integral
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TransformState {
+    /// Last reading
+    previous: Option<(DateTime<Utc>, f64)>,
+    integral: f64,
+}
+
+impl TransformState {
+    /// Transforms a reading, or returns None if there is nothing to show yet.
+    ///
+    /// Readings from the same chunk share a timestamp, so only readings with a new timestamp produce a
+    /// derivative, and readings without one add nothing to the integral.
+    pub fn next(&mut self, kind: Transform, stamp: DateTime<Utc>, data: f64) -> Option<f64> {
+        let previous = self.previous;
+        if kind != Transform::None {
+            self.previous = Some((stamp, data));
+        }
+
+        match kind {
+            Transform::None => Some(data),
+            Transform::Derivative => {
+                let (previous_stamp, value) = previous?;
+                let dt = seconds_between(previous_stamp, stamp);
+                (dt > 0.0).then(|| (data - value) / dt)
+            }
+            Transform::Integral => {
+                if let Some((previous_stamp, value)) = previous {
+                    let dt = seconds_between(previous_stamp, stamp);
+                    self.integral += (value + data) / 2.0 * dt;
+                }
+                Some(self.integral)
+            }
+        }
+    }
+
+    pub fn reset_integral(&mut self) {
+        self.integral = 0.0;
+    }
+}
+
+/// Per-channel transform state
+pub struct Transforms {
+    /// Untransformed name of each channel
+    names: Vec<String>,
+    kinds: Vec<Transform>,
+    states: Vec<TransformState>,
+    /// Channel selected in the gui
+    selected: usize,
+    /// Channels removed while running, which are no longer listed
+    removed: Vec<usize>,
+}
+
+impl Transforms {
+    pub fn new(names: Vec<String>) -> Self {
+        let num_channels = names.len();
+
+        Self {
+            names,
+            kinds: vec![Transform::None; num_channels],
+            states: vec![TransformState::default(); num_channels],
+            selected: 0,
+            removed: Vec::new(),
+        }
+    }
+
+    /// Adds an untransformed channel added while running.
+    pub fn add_channel(&mut self, name: String) {
+        self.names.push(name);
+        self.kinds.push(Transform::None);
+        self.states.push(TransformState::default());
+    }
+
+    /// Stops listing a channel removed while running.
+    pub fn remove_channel(&mut self, channel: usize) {
+        self.removed.push(channel);
+    }
+
+    /// Changes the transform of a channel, restarting its state.
+    pub fn set(&mut self, channel: usize, transform: Transform) {
+        self.kinds[channel] = transform;
+        self.states[channel] = TransformState::default();
+    }
+
+    pub const fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, channel: usize) {
+        self.selected = channel;
+    }
+
+    /// Name of a channel including its transform
+    pub fn name(&self, channel: usize) -> String {
+        format!("{}{}", self.names[channel], self.kinds[channel].suffix())
+    }
+
+    /// Zeros every running integral.
+    pub fn reset_integrals(&mut self) {
+        self.states
+            .iter_mut()
+            .for_each(TransformState::reset_integral);
+    }
+
+    /// Transforms a reading, or returns None if there is nothing to show yet.
+    pub fn apply(&mut self, mut reading: Data) -> Option<Data> {
+        let channel = reading.channel;
+
+        // Text is shown as it is
+        let data = match reading.data.number() {
+            Some(data) => data,
+            None => return Some(reading),
+        };
+        if self.kinds[channel] == Transform::None {
+            return Some(reading);
+        }
+
+        reading.data =
+            Value::Number(self.states[channel].next(self.kinds[channel], reading.stamp, data)?);
+        Some(reading)
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let channels: Vec<usize> = (0..self.names.len())
+            .filter(|c| !self.removed.contains(c))
+            .collect();
+
+        row![
+            Text::new("Transform channel: "),
+            PickList::new(channels, Some(self.selected), Message::TransformChannel),
+            Text::new(" as "),
+            PickList::new(
+                &Transform::ALL[..],
+                Some(self.kinds[self.selected]),
+                Message::TransformKind
+            ),
+            Text::new(" "),
+            Button::new("Reset integrals").on_press(Message::ResetIntegrals),
+        ]
+        .into()
+    }
+}
+
+/// Runtime state for the transforms written to the CSV, which the gui does not see
+pub struct Transforming {
+    /// Output channel, input channel, kind and state of each transform
+    transforms: Vec<(usize, usize, Transform, TransformState)>,
+    /// Integral resets from the gui seen so far
+    resets: u64,
+}
+
+impl Transforming {
+    pub fn new(config: &Config) -> Self {
+        let transforms = config
+            .channels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match &c.source {
+                Source::Transform(input, kind) => {
+                    Some((i, *input, *kind, TransformState::default()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            transforms,
+            resets: config.integral_resets,
+        }
+    }
+
+    /// Transforms a batch of readings, appending the transformed readings to the batch. Integrals are
+    /// zeroed if the gui has reset them `resets` times since last seen.
+    pub fn process(&mut self, batch: &mut Vec<Data>, resets: u64) {
+        if self.transforms.is_empty() {
+            return;
+        }
+
+        if resets != self.resets {
+            self.resets = resets;
+            self.transforms
+                .iter_mut()
+                .for_each(|(_, _, _, state)| state.reset_integral());
+        }
+
+        let mut i = 0;
+        while i < batch.len() {
+            let (stamp, channel) = (batch[i].stamp, batch[i].channel);
+            let data = batch[i].data.number();
+            i += 1;
+
+            // Only numbers can be transformed
+            let data = match data {
+                Some(data) => data,
+                None => continue,
+            };
+
+            for (output, input, kind, state) in &mut self.transforms {
+                if *input == channel {
+                    if let Some(data) = state.next(*kind, stamp, data) {
+                        batch.push(Data {
+                            stamp,
+                            channel: *output,
+                            data: Value::Number(data),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn seconds_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_microseconds().unwrap_or(0) as f64 / 1e6
+}
@@ -0,0 +1,150 @@
+//! Watchdog that notices channels which have stopped receiving data
+
+use crate::alarm::{run_command, AlarmActions};
+use crate::extractor::Config;
+use std::time::{Duration, Instant};
+
+/// Tracks when each channel last had a reading
+pub struct Watchdog {
+    timeout: Duration,
+    names: Vec<String>,
+    /// Time of the last reading on each channel, or when watching started
+    last_seen: Vec<Instant>,
+    stalled: Vec<bool>,
+    /// Channels read from stdin, and not removed while running. Computed channels only stall when their
+    /// inputs do, so are not watched.
+    watched: Vec<bool>,
+    actions: AlarmActions,
+}
+
+impl Watchdog {
+    pub fn new(config: &Config, timeout: Duration) -> Self {
+        let now = Instant::now();
+
+        Self {
+            timeout,
+            names: config.channel_labels(),
+            last_seen: vec![now; config.channels.len()],
+            stalled: vec![false; config.channels.len()],
+            watched: config
+                .channels
+                .iter()
+                .map(|c| c.source.is_input())
+                .collect(),
+            actions: config.alarm.clone(),
+        }
+    }
+
+    /// How often the watchdog should be checked to notice a stall promptly
+    pub fn tick_interval(&self) -> Duration {
+        (self.timeout / 4).max(Duration::from_millis(50))
+    }
+
+    /// Starts watching a channel added while running.
+    pub fn add_channel(&mut self, name: String) {
+        self.names.push(name);
+        self.last_seen.push(Instant::now());
+        self.stalled.push(false);
+        self.watched.push(true);
+    }
+
+    /// Stops watching a channel that was removed.
+    pub fn remove_channel(&mut self, channel: usize) {
+        self.watched[channel] = false;
+        self.stalled[channel] = false;
+    }
+
+    /// Records a reading on a channel, clearing any stall.
+    pub fn feed(&mut self, channel: usize) {
+        self.last_seen[channel] = Instant::now();
+        if self.stalled[channel] {
+            self.stalled[channel] = false;
+            log::info!("{} is receiving data again", self.names[channel]);
+        }
+    }
+
+    pub fn is_stalled(&self, channel: usize) -> bool {
+        self.stalled[channel]
+    }
+
+    /// Names of the stalled channels
+    pub fn stalled_names(&self) -> Vec<&str> {
+        self.names
+            .iter()
+            .zip(&self.stalled)
+            .filter(|(_, stalled)| **stalled)
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    pub const fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Marks channels that have gone without data for longer than the timeout as stalled, running the
+    /// alarm actions for each that just stalled. Returns true if any channel changed state.
+    pub fn check(&mut self, now: Instant) -> bool {
+        let mut changed = false;
+
+        for channel in 0..self.stalled.len() {
+            let silent = now.saturating_duration_since(self.last_seen[channel]);
+            if self.stalled[channel] || !self.watched[channel] || silent <= self.timeout {
+                continue;
+            }
+
+            self.stalled[channel] = true;
+            changed = true;
+
+            let name = &self.names[channel];
+            log::warn!("{name} has had no data for {}ms", silent.as_millis());
+            if self.actions.stderr {
+                eprintln!(
+                    "cliplot: {name} has had no data for {}ms",
+                    silent.as_millis()
+                );
+            }
+
+            if let Some(command) = &self.actions.command {
+                run_command(
+                    command,
+                    &[
+                        ("CLIPLOT_ALARM", "stall".to_string()),
+                        ("CLIPLOT_CHANNEL", channel.to_string()),
+                        ("CLIPLOT_NAME", name.clone()),
+                        ("CLIPLOT_STALL_MS", silent.as_millis().to_string()),
+                    ],
+                );
+            }
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractor::{Channel, Source};
+
+    #[test]
+    fn only_watches_input_channels() {
+        let mut config = Config::default();
+        config
+            .channels
+            .push(Channel::new("removed", Source::Removed));
+        config.channels.push(Channel::new(
+            "filtered",
+            Source::Filter(0, crate::filter::FilterKind::Exponential(0.5)),
+        ));
+        config
+            .channels
+            .push(Channel::regex(3, Config::default_regex()));
+
+        let timeout = Duration::from_millis(100);
+        let mut watchdog = Watchdog::new(&config, timeout);
+        watchdog.remove_channel(3);
+
+        assert!(watchdog.check(Instant::now() + timeout * 2));
+        assert_eq!(watchdog.stalled_names(), ["channel 0"]);
+    }
+}
@@ -0,0 +1,250 @@
+//! Scrolling waterfall heatmap of an array channel, with one row per frame, or per slice of history when
+//! frames come faster than the rows kept can cover
+
+use crate::colormap::Colormap;
+use crate::extractor::{Config, Data, Source, Value};
+use crate::interface::{Message, SignalChart, FONT_BOLD, FONT_REGULAR};
+use iced::widget::{canvas::Cache, canvas::Frame, canvas::Geometry, row, Column, PickList, Text};
+use iced::{Element, Length, Size};
+use plotters::prelude::ChartBuilder;
+use plotters_iced::plotters_backend::DrawingBackend;
+use plotters_iced::{Chart, ChartWidget};
+use std::collections::VecDeque;
+
+/// Most columns a frame is pooled into, to keep drawing cheap.
+const MAX_COLUMNS: usize = 128;
+
+/// Most rows kept. Frames that come faster than the history divided into this many rows are pooled into
+/// one row, so the rows always span the whole history.
+const MAX_ROWS: usize = 256;
+
+/// Settings for the waterfall panel
+#[derive(Copy, Clone, Debug)]
+pub struct WaterfallSettings {
+    /// Array channel to show
+    pub channel: usize,
+    pub colormap: Colormap,
+    /// How much time the waterfall scrolls over
+    pub history_ms: u64,
+}
+
+/// One or more frames in the waterfall
+struct Row {
+    /// Time span this row covers, in the same time base as the signal chart
+    start_ms: i64,
+    end_ms: i64,
+    /// Number of array elements each cell covers
+    stride: usize,
+    /// Value of each cell, the peak of the elements and frames it covers
+    cells: Vec<f64>,
+}
+
+/// Widget that displays the frames of an array channel as a heatmap, newest at the top
+pub struct WaterfallChart {
+    cache: Cache,
+    /// Array channels that can be selected
+    arrays: Vec<usize>,
+    settings: WaterfallSettings,
+    /// Rows ordered oldest first
+    rows: VecDeque<Row>,
+}
+
+impl WaterfallChart {
+    pub fn new(config: &Config, settings: WaterfallSettings) -> Self {
+        let arrays = config
+            .channels
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| matches!(c.source, Source::Array(_)))
+            .map(|(i, _)| i)
+            .collect();
+
+        Self {
+            cache: Cache::new(),
+            arrays,
+            settings,
+            rows: VecDeque::new(),
+        }
+    }
+
+    /// Changes the array shown, which clears the history.
+    pub fn set_channel(&mut self, channel: usize) {
+        self.settings.channel = channel;
+        self.rows.clear();
+        self.cache.clear();
+    }
+
+    pub fn set_colormap(&mut self, colormap: Colormap) {
+        self.settings.colormap = colormap;
+        self.cache.clear();
+    }
+
+    /// Adds each frame of the selected array in a batch of readings, as a new row or pooled into the latest.
+    pub fn push(&mut self, data: &[Data], signal: &SignalChart) {
+        for reading in data.iter().filter(|d| d.channel == self.settings.channel) {
+            let values = match &reading.data {
+                Value::Array(values) => values,
+                _ => continue,
+            };
+
+            let now = (signal.seconds_since_start(reading.stamp) * 1000.0) as i64;
+            let stride = values.len().div_ceil(MAX_COLUMNS);
+            let cells: Vec<f64> = values
+                .chunks(stride)
+                .map(|chunk| chunk.iter().copied().fold(f64::NEG_INFINITY, f64::max))
+                .collect();
+
+            // Pool frames into the latest row until it covers its slice of the history
+            let row_ms = (self.settings.history_ms / MAX_ROWS as u64) as i64;
+            match self.rows.back_mut() {
+                Some(last)
+                    if now - last.start_ms < row_ms
+                        && last.stride == stride
+                        && last.cells.len() == cells.len() =>
+                {
+                    last.end_ms = now;
+                    for (pooled, value) in last.cells.iter_mut().zip(cells) {
+                        *pooled = pooled.max(value);
+                    }
+                }
+                last => {
+                    // Rows reach down to the previous frame, so the heatmap has no gaps
+                    let start_ms = last.map_or(now, |last| last.end_ms);
+                    self.rows.push_back(Row {
+                        start_ms,
+                        end_ms: now,
+                        stride,
+                        cells,
+                    });
+                }
+            }
+
+            // Scroll off old rows
+            while let Some(first) = self.rows.front() {
+                if first.end_ms < now - self.settings.history_ms as i64
+                    || self.rows.len() > MAX_ROWS
+                {
+                    self.rows.pop_front();
+                } else {
+                    break;
+                }
+            }
+
+            self.cache.clear();
+        }
+    }
+
+    /// Range of values currently displayed
+    fn value_range(&self) -> Option<(f64, f64)> {
+        let (low, high) = self
+            .rows
+            .iter()
+            .flat_map(|r| r.cells.iter())
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(*v), hi.max(*v))
+            });
+
+        (low.is_finite() && high.is_finite()).then_some((low, high))
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let range = self.value_range().map_or_else(String::new, |(low, high)| {
+            format!(" Range: {:.4} to {:.4}", low, high)
+        });
+
+        Column::new()
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .spacing(5)
+            .push(row![
+                Text::new("Array channel: "),
+                PickList::new(
+                    self.arrays.clone(),
+                    Some(self.settings.channel),
+                    Message::WaterfallChannel
+                ),
+                Text::new(" Colormap: "),
+                PickList::new(
+                    &Colormap::ALL[..],
+                    Some(self.settings.colormap),
+                    Message::WaterfallColormap
+                ),
+                Text::new(range),
+            ])
+            .push(ChartWidget::new(self).height(Length::Fill).resolve_font(
+                |_, style| match style {
+                    plotters_iced::plotters_backend::FontStyle::Bold => FONT_BOLD,
+                    _ => FONT_REGULAR,
+                },
+            ))
+            .into()
+    }
+}
+
+impl Chart<Message> for WaterfallChart {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut chart: ChartBuilder<DB>) {
+        use plotters::{prelude::*, style::Color};
+
+        let latest = self.rows.back().map_or(0, |r| r.end_ms);
+        let oldest = latest - self.settings.history_ms as i64;
+        let len = self
+            .rows
+            .iter()
+            .map(|r| r.cells.len() * r.stride)
+            .max()
+            .unwrap_or(1);
+
+        let mut chart = chart
+            .x_label_area_size(28)
+            .y_label_area_size(28)
+            .margin(20)
+            .build_cartesian_2d(0..len, oldest..latest)
+            .expect("failed to build chart");
+
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .axis_style(ShapeStyle::from(BLUE.mix(0.80)).stroke_width(1))
+            .x_desc("Index")
+            .y_labels(10)
+            .y_label_style(
+                ("sans-serif", 15)
+                    .into_font()
+                    .color(&BLUE.mix(0.80))
+                    .transform(FontTransform::Rotate90),
+            )
+            .y_label_formatter(&|d| format!("{}ms", d))
+            .x_labels(10)
+            .x_label_style(("sans-serif", 15).into_font().color(&BLUE.mix(0.80)))
+            .draw()
+            .expect("failed to draw chart mesh");
+
+        let (low, high) = match self.value_range() {
+            Some(range) => range,
+            None => return,
+        };
+
+        let colormap = self.settings.colormap;
+        chart
+            .draw_series(self.rows.iter().flat_map(|r| {
+                r.cells.iter().enumerate().map(move |(i, value)| {
+                    Rectangle::new(
+                        [
+                            (i * r.stride, r.start_ms.max(oldest)),
+                            ((i + 1) * r.stride, r.end_ms),
+                        ],
+                        colormap.color_in(*value, low, high).filled(),
+                    )
+                })
+            }))
+            .expect("failed to draw waterfall");
+    }
+
+    #[inline]
+    fn draw<F: Fn(&mut Frame)>(&self, bounds: Size, draw_fn: F) -> Geometry {
+        self.cache.draw(bounds, draw_fn)
+    }
+}
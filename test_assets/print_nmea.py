@@ -0,0 +1,55 @@
+# Script that writes NMEA 0183 GGA, RMC and VTG sentences of a receiver driving in a circle, with a time delay
+# between fixes. A character of one sentence in fifty is corrupted, as a noisy link would.
+
+import datetime
+import functools
+import math
+import random
+import sys
+import time
+
+if len(sys.argv) < 2:
+    print("Usage: python3 print_nmea.py <delta_seconds>")
+    exit(-1)
+
+
+def sentence(body):
+    checksum = functools.reduce(lambda sum, c: sum ^ ord(c), body, 0)
+    line = "$%s*%02X" % (body, checksum)
+    if random.random() < 0.02:
+        i = random.randrange(1, len(line) - 3)
+        line = line[:i] + chr(ord(line[i]) ^ 1) + line[i + 1:]
+    return line + "\r\n"
+
+
+def coordinate(degrees, digits):
+    hemisphere = 0 if degrees >= 0 else 1
+    degrees = abs(degrees)
+    minutes = (degrees - int(degrees)) * 60
+    return "%0*d%07.4f" % (digits, int(degrees), minutes), hemisphere
+
+
+delta_s = float(sys.argv[1])
+t = 0.0
+while True:
+    now = datetime.datetime.now(datetime.timezone.utc)
+    stamp = now.strftime("%H%M%S.") + "%02d" % (now.microsecond // 10000)
+    date = now.strftime("%d%m%y")
+
+    lat, ns = coordinate(51.5 + 0.001 * math.sin(t / 10), 2)
+    lon, ew = coordinate(-0.12 + 0.001 * math.cos(t / 10), 3)
+    ns, ew = "NS"[ns], "EW"[ew]
+    altitude = 35 + 2 * math.sin(t)
+    course = (math.degrees(t / 10) + 90) % 360
+    knots = 12 + math.sin(t / 3)
+
+    sys.stdout.write(
+        sentence("GPGGA,%s,%s,%s,%s,%s,1,%d,0.9,%.1f,M,47.0,M,," % (stamp, lat, ns, lon, ew, 8 + int(t) % 3, altitude))
+    )
+    sys.stdout.write(
+        sentence("GPRMC,%s,A,%s,%s,%s,%s,%.1f,%.1f,%s,1.2,W,A" % (stamp, lat, ns, lon, ew, knots, course, date))
+    )
+    sys.stdout.write(sentence("GPVTG,%.1f,T,,M,%.1f,N,%.1f,K,A" % (course, knots, knots * 1.852)))
+    sys.stdout.flush()
+    time.sleep(delta_s)
+    t += delta_s
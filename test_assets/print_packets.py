@@ -0,0 +1,66 @@
+# Script that writes binary packets matching sensor_layout.txt, with a time delay. With `cobs`, packets are
+# COBS framed with a CRC-32 instead, matching sensor_layout_cobs.txt, and some are corrupted.
+
+import math
+import random
+import struct
+import sys
+import time
+import zlib
+
+if len(sys.argv) < 2:
+    print("Usage: python3 print_packets.py <delta_seconds> [cobs]")
+    exit(-1)
+
+
+def crc16_ccitt(data):
+    crc = 0xFFFF
+    for byte in data:
+        crc ^= byte << 8
+        for _ in range(8):
+            crc = ((crc << 1) ^ 0x1021) if crc & 0x8000 else crc << 1
+            crc &= 0xFFFF
+    return crc
+
+
+def cobs_encode(data):
+    out = bytearray([0])
+    code_index = 0
+    for byte in data:
+        if byte == 0:
+            out[code_index] = len(out) - code_index
+            code_index = len(out)
+            out.append(0)
+        else:
+            out.append(byte)
+            if len(out) - code_index == 0xFF:
+                out[code_index] = 0xFF
+                code_index = len(out)
+                out.append(0)
+    out[code_index] = len(out) - code_index
+    return bytes(out) + b"\x00"
+
+
+cobs = len(sys.argv) > 2 and sys.argv[2] == "cobs"
+delta_s = float(sys.argv[1])
+t = 0.0
+while True:
+    payload = struct.pack("<f", 20.0 + 5.0 * math.sin(t))
+    payload += struct.pack(">H", int(1013 + 10 * math.cos(t / 3)))
+    payload += b"\x00"
+    payload += struct.pack("<h", int(1000 * math.sin(t * 4)))
+
+    covered = bytes([len(payload)]) + payload
+    if cobs:
+        frame = bytearray(covered + struct.pack("<I", zlib.crc32(covered)))
+        # Flip a bit in one frame in fifty, as a noisy link would
+        if random.random() < 0.02:
+            frame[random.randrange(len(frame))] ^= 1 << random.randrange(8)
+        packet = cobs_encode(frame)
+    else:
+        packet = b"\xAA\x55" + covered + struct.pack("<H", crc16_ccitt(covered))
+
+    sys.stdout.buffer.write(packet)
+    sys.stdout.flush()
+    time.sleep(delta_s)
+    t += delta_s
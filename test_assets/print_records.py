@@ -0,0 +1,73 @@
+# Script that writes MessagePack or CBOR maps of sensor readings, with a time delay. Readings are under
+# `temp`, `imu.accel.0` to `imu.accel.2` and `battery`, which is sent as a string.
+
+import math
+import struct
+import sys
+import time
+
+if len(sys.argv) < 3 or sys.argv[2] not in ("msgpack", "cbor"):
+    print("Usage: python3 print_records.py <delta_seconds> <msgpack|cbor>")
+    exit(-1)
+
+
+def msgpack_encode(value):
+    if isinstance(value, float):
+        return b"\xcb" + struct.pack(">d", value)
+    if isinstance(value, int):
+        if 0 <= value < 0x80:
+            return bytes([value])
+        if -32 <= value < 0:
+            return struct.pack(">b", value)
+        return b"\xd2" + struct.pack(">i", value)
+    if isinstance(value, str):
+        data = value.encode()
+        return bytes([0xA0 | len(data)]) + data
+    if isinstance(value, list):
+        return bytes([0x90 | len(value)]) + b"".join(msgpack_encode(v) for v in value)
+    if isinstance(value, dict):
+        return bytes([0x80 | len(value)]) + b"".join(
+            msgpack_encode(k) + msgpack_encode(v) for k, v in value.items()
+        )
+    raise TypeError(value)
+
+
+def cbor_head(major, n):
+    if n < 24:
+        return bytes([major << 5 | n])
+    if n < 0x100:
+        return bytes([major << 5 | 24, n])
+    return bytes([major << 5 | 26]) + struct.pack(">I", n)
+
+
+def cbor_encode(value):
+    if isinstance(value, float):
+        return b"\xfb" + struct.pack(">d", value)
+    if isinstance(value, int):
+        return cbor_head(0, value) if value >= 0 else cbor_head(1, -1 - value)
+    if isinstance(value, str):
+        data = value.encode()
+        return cbor_head(3, len(data)) + data
+    if isinstance(value, list):
+        return cbor_head(4, len(value)) + b"".join(cbor_encode(v) for v in value)
+    if isinstance(value, dict):
+        return cbor_head(5, len(value)) + b"".join(
+            cbor_encode(k) + cbor_encode(v) for k, v in value.items()
+        )
+    raise TypeError(value)
+
+
+encode = msgpack_encode if sys.argv[2] == "msgpack" else cbor_encode
+delta_s = float(sys.argv[1])
+t = 0.0
+while True:
+    record = {
+        "temp": 20.0 + 5.0 * math.sin(t),
+        "imu": {"accel": [math.sin(t * 4), math.cos(t * 4), -1]},
+        "battery": "%.2f" % (4.2 - t / 1000),
+    }
+
+    sys.stdout.buffer.write(encode(record))
+    sys.stdout.flush()
+    time.sleep(delta_s)
+    t += delta_s